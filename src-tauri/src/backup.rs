@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+use crate::fs_util;
+
+const BACKUP_EXTENSION: &str = "enc";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.6fZ";
+// Hex digits of the keys file path hash in backup names
+const PATH_HASH_LEN: usize = 16;
+
+// A backup copy of a keys file, named
+// <keys file stem>-<path hash>-<UTC timestamp>.enc
+pub struct Backup {
+    pub file_name: String,
+    pub path: PathBuf,
+    pub created: DateTime<Utc>,
+    pub size: u64,
+}
+
+// Copy the current keys file into the backups directory, then delete the
+// oldest backups of that file beyond the limit. Does nothing if the keys
+// file doesn't exist yet or the limit is zero.
+pub fn create_backup(backups_dir: &Path, keys_file: &Path, limit: usize) -> Result<(), String> {
+    if limit == 0 || !keys_file.exists() {
+        return Ok(());
+    }
+
+    let content = fs::read(keys_file)
+        .map_err(|e| format!("Failed to read keys file for backup: {}", e))?;
+
+    fs_util::create_private_dir(backups_dir)
+        .map_err(|e| format!("Failed to create backups directory: {}", e))?;
+
+    let file_name = format!(
+        "{}-{}.{}",
+        backup_prefix(keys_file),
+        Utc::now().format(TIMESTAMP_FORMAT),
+        BACKUP_EXTENSION
+    );
+    fs_util::write_atomic(&backups_dir.join(&file_name), &content)
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+
+    // Backups are listed newest first
+    for old_backup in list_backups(backups_dir, keys_file)?.into_iter().skip(limit) {
+        fs::remove_file(&old_backup.path)
+            .map_err(|e| format!("Failed to remove old backup {}: {}", old_backup.file_name, e))?;
+    }
+
+    Ok(())
+}
+
+// List the backups of a keys file, newest first
+pub fn list_backups(backups_dir: &Path, keys_file: &Path) -> Result<Vec<Backup>, String> {
+    if !backups_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = format!("{}-", backup_prefix(keys_file));
+    let mut backups = Vec::new();
+
+    for entry in fs::read_dir(backups_dir)
+        .map_err(|e| format!("Failed to read backups directory: {}", e))? {
+        let entry = entry.map_err(|e| format!("Failed to read backups directory entry: {}", e))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        let timestamp = match file_name.strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(&format!(".{}", BACKUP_EXTENSION))) {
+            Some(timestamp) => timestamp,
+            None => continue,
+        };
+        let created = match NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT) {
+            Ok(created) => created.and_utc(),
+            Err(_) => continue,
+        };
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+
+        backups.push(Backup {
+            file_name,
+            path: entry.path(),
+            created,
+            size,
+        });
+    }
+
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created));
+    Ok(backups)
+}
+
+// Find a backup of a keys file by its file name
+pub fn find_backup(backups_dir: &Path, keys_file: &Path, file_name: &str) -> Result<Backup, String> {
+    list_backups(backups_dir, keys_file)?
+        .into_iter()
+        .find(|backup| backup.file_name == file_name)
+        .ok_or_else(|| format!("Backup not found: {}", file_name))
+}
+
+// Backups are grouped by the keys file's full path, so files with the same
+// name in different directories don't mix. The name is kept for readability.
+fn backup_prefix(keys_file: &Path) -> String {
+    let stem = keys_file.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "keys".to_string());
+    let path = std::path::absolute(keys_file).unwrap_or_else(|_| keys_file.to_path_buf());
+    let hash = hex::encode(Sha256::digest(path.to_string_lossy().as_bytes()));
+    format!("{}-{}", stem, &hash[..PATH_HASH_LEN])
+}
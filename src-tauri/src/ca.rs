@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use serde::Deserialize;
+use ssh_key::certificate::{Builder, CertType};
+use ssh_key::{PrivateKey, PublicKey};
+use crate::private_key;
+
+// Extensions ssh-keygen puts in user certificates unless told otherwise
+pub const DEFAULT_USER_EXTENSIONS: &[&str] = &[
+    "permit-X11-forwarding",
+    "permit-agent-forwarding",
+    "permit-port-forwarding",
+    "permit-pty",
+    "permit-user-rc",
+];
+
+// What to put in a certificate
+#[derive(Debug, Clone, Deserialize)]
+pub struct CertificateRequest {
+    // "user" or "host"
+    pub cert_type: String,
+    pub key_id: String,
+    // User names or host names; at least one is required
+    pub principals: Vec<String>,
+    // Defaults to "always", like ssh-keygen, so that the certificate isn't
+    // refused by servers whose clocks are behind
+    #[serde(default)]
+    pub valid_after: Option<DateTime<Utc>>,
+    // Defaults to no expiry
+    #[serde(default)]
+    pub valid_before: Option<DateTime<Utc>>,
+    // Defaults to one more than the highest serial the CA has issued
+    #[serde(default)]
+    pub serial: Option<u64>,
+    // Extension names. User certificates default to the same set as
+    // ssh-keygen; host certificates have none.
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+    // e.g. "force-command" or "source-address"
+    #[serde(default)]
+    pub critical_options: BTreeMap<String, String>,
+}
+
+// Sign `subject` (a public key line) with the CA key, returning the
+// certificate as a `-cert.pub` line
+pub fn sign(ca_key: &PrivateKey, passphrase: Option<&str>, subject: &str, request: &CertificateRequest, serial: u64) -> Result<String, String> {
+    let ca_key = private_key::unlock(ca_key, passphrase)?;
+
+    let subject = PublicKey::from_openssh(subject.trim())
+        .map_err(|e| format!("Invalid SSH public key: {}", e))?;
+
+    let cert_type = match request.cert_type.as_str() {
+        "user" => CertType::User,
+        "host" => CertType::Host,
+        other => return Err(format!("Unknown certificate type '{}', expected 'user' or 'host'", other)),
+    };
+    if request.key_id.trim().is_empty() {
+        return Err("A key ID is required".to_string());
+    }
+    // A certificate without principals is valid for every user or host
+    if request.principals.iter().all(|p| p.trim().is_empty()) {
+        return Err("At least one principal is required".to_string());
+    }
+
+    // 0 and the maximum value mean no bound, as in ssh-keygen
+    let valid_after = match request.valid_after {
+        Some(after) => unix_seconds(after)?,
+        None => 0,
+    };
+    let valid_before = match request.valid_before {
+        Some(before) => unix_seconds(before)?,
+        None => i64::MAX as u64,
+    };
+    if valid_before <= valid_after {
+        return Err("The certificate must expire after it becomes valid".to_string());
+    }
+
+    let mut builder = Builder::new_with_random_nonce(&mut OsRng, subject.key_data().clone(), valid_after, valid_before)
+        .map_err(certificate_error)?;
+    builder.cert_type(cert_type).map_err(certificate_error)?;
+    builder.serial(serial).map_err(certificate_error)?;
+    builder.key_id(request.key_id.trim()).map_err(certificate_error)?;
+    builder.comment(subject.comment()).map_err(certificate_error)?;
+    for principal in request.principals.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        builder.valid_principal(principal).map_err(certificate_error)?;
+    }
+    for (name, value) in &request.critical_options {
+        builder.critical_option(name, value).map_err(certificate_error)?;
+    }
+
+    let default_extensions = match cert_type {
+        CertType::User => DEFAULT_USER_EXTENSIONS,
+        CertType::Host => &[],
+    };
+    let extensions: Vec<&str> = match &request.extensions {
+        Some(extensions) => extensions.iter().map(String::as_str).collect(),
+        None => default_extensions.to_vec(),
+    };
+    for extension in extensions {
+        builder.extension(extension, "").map_err(certificate_error)?;
+    }
+
+    let certificate = builder.sign(&ca_key).map_err(certificate_error)?;
+    certificate.to_openssh()
+        .map_err(certificate_error)
+}
+
+fn unix_seconds(time: DateTime<Utc>) -> Result<u64, String> {
+    u64::try_from(time.timestamp())
+        .map_err(|_| "Certificate validity can't start before 1970".to_string())
+}
+
+fn certificate_error(e: ssh_key::Error) -> String {
+    format!("Failed to create certificate: {}", e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::public_key;
+
+    const SUBJECT: &str = include_str!("../tests/fixtures/keys/id_ed25519.pub");
+
+    fn request(json: serde_json::Value) -> CertificateRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn signs_certificates_readable_by_the_parser() {
+        let ca = private_key::generate("ed25519", None, "ca@example.com").unwrap();
+        let request = request(serde_json::json!({
+            "cert_type": "user",
+            "key_id": " alice@example.com ",
+            "principals": ["alice", " ", "deploy"],
+            "critical_options": { "force-command": "/usr/bin/true" },
+        }));
+
+        let certificate = sign(&ca, None, SUBJECT, &request, 42).unwrap();
+        let parsed = public_key::parse(&certificate).unwrap();
+        assert_eq!(parsed.blob, public_key::parse(SUBJECT).unwrap().blob);
+        assert_eq!(parsed.comment.as_deref(), Some("ed25519@example.com"));
+
+        let cert = parsed.certificate.unwrap();
+        assert_eq!(cert.cert_type, "user");
+        assert_eq!(cert.serial, 42);
+        assert_eq!(cert.key_id, "alice@example.com");
+        assert_eq!(cert.principals, ["alice", "deploy"]);
+        // Valid from "always" to "forever" by default
+        assert_eq!(cert.valid_after, None);
+        assert_eq!(cert.valid_before, None);
+        assert_eq!(cert.critical_options["force-command"], "/usr/bin/true");
+        assert_eq!(cert.extensions.keys().map(String::as_str).collect::<Vec<_>>(), DEFAULT_USER_EXTENSIONS);
+        assert_eq!(cert.ca_fingerprint, private_key::fingerprint(&ca));
+    }
+
+    #[test]
+    fn keeps_requested_validity_and_extensions() {
+        let ca = private_key::generate("ed25519", None, "").unwrap();
+        let request = request(serde_json::json!({
+            "cert_type": "host",
+            "key_id": "web",
+            "principals": ["web.example.com"],
+            "valid_after": "2025-01-01T00:00:00Z",
+            "valid_before": "2026-01-01T00:00:00Z",
+        }));
+
+        let cert = public_key::parse(&sign(&ca, None, SUBJECT, &request, 1).unwrap()).unwrap().certificate.unwrap();
+        assert_eq!(cert.cert_type, "host");
+        assert_eq!(cert.valid_after, request.valid_after);
+        assert_eq!(cert.valid_before, request.valid_before);
+        assert!(cert.extensions.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let ca = private_key::generate("ed25519", None, "").unwrap();
+        let valid = serde_json::json!({ "cert_type": "user", "key_id": "alice", "principals": ["alice"] });
+
+        let mut json = valid.clone();
+        json["principals"] = serde_json::json!([" "]);
+        assert!(sign(&ca, None, SUBJECT, &request(json), 1).unwrap_err().contains("principal"));
+
+        let mut json = valid.clone();
+        json["key_id"] = serde_json::json!("");
+        assert!(sign(&ca, None, SUBJECT, &request(json), 1).unwrap_err().contains("key ID"));
+
+        let mut json = valid.clone();
+        json["cert_type"] = serde_json::json!("robot");
+        assert!(sign(&ca, None, SUBJECT, &request(json), 1).is_err());
+
+        let mut json = valid;
+        json["valid_after"] = serde_json::json!("2026-01-01T00:00:00Z");
+        json["valid_before"] = serde_json::json!("2025-01-01T00:00:00Z");
+        assert!(sign(&ca, None, SUBJECT, &request(json), 1).unwrap_err().contains("expire"));
+    }
+}
@@ -1,22 +1,32 @@
-use crate::{SshKey, SshKeyUpdate, SshKeyLocation};
+use crate::{SshKey, SshKeyUpdate, SshKeyLocation, SshKeyFile};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::env;
-use std::sync::Mutex;
-use chrono::Utc;
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use aes::Aes256;
-use aes::cipher::{
-    BlockEncrypt, BlockDecrypt,
-    KeyInit,
-    generic_array::GenericArray,
-};
-use base64::{Engine as _, engine::general_purpose};
-use rand::Rng;
+use crate::backup;
+use crate::ca::{self, CertificateRequest};
+use crate::fs_util;
+use crate::key_format::{self, PublicKeyFormat};
+use crate::krl::{self, Krl, Revocation, RevokedSerial};
+use crate::machine;
+use crate::merge::{self, MergeEntry, MergeResolution};
+use crate::ppk;
+use crate::private_key::{self, PrivateKeyFormat};
+use crate::public_key::{self, Certificate, CertificateValidity};
+use crate::settings::{self, Settings};
+use crate::strength::{self, KeyPolicy, KeyStrength};
+use crate::vault::{self, KeySource, Kdf, VaultFile};
 use once_cell::sync::Lazy;
 use rfd::FileDialog;
-use sha2::{Sha256, Digest};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh_key::PrivateKey;
+use zeroize::Zeroize;
 
 #[derive(Serialize)]
 pub struct ImportResult {
@@ -24,95 +34,235 @@ pub struct ImportResult {
     pub imported_count: usize,
     pub duplicate_count: usize,
     pub total_in_store: usize,
+    pub duplicates: Vec<DuplicateKey>,
 }
 
+// An incoming key that was skipped because the store already holds the same key
+#[derive(Serialize, Clone)]
+pub struct DuplicateKey {
+    pub name: String,
+    pub fingerprint: String,
+    // The stored entry it matched
+    pub existing_id: String,
+    pub existing_name: String,
+}
 
-// Machine-specific encryption key (derived from machine ID)
-static MACHINE_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
-    let machine_id = get_machine_id();
-    let mut hasher = Sha256::new();
-    hasher.update(machine_id.as_bytes());
-    hasher.update(b"ssh-kim-machine-key");
-    let result = hasher.finalize();
-    let mut key = [0u8; 32];
-    key.copy_from_slice(&result);
-    key
+
+// Machine-specific encryption key (derived from the machine ID and per-install secret)
+static MACHINE_KEY: Lazy<Result<[u8; 32], String>> = Lazy::new(|| {
+    let ssh_kim_dir = get_ssh_kim_dir()?;
+    machine::derive_machine_key(&ssh_kim_dir)
 });
 
-// Password-based encryption key (when user sets a password)
-static PASSWORD_KEY: Lazy<Mutex<Option<[u8; 32]>>> = Lazy::new(|| Mutex::new(None));
+// Key derived from the master password, with the KDF settings and salt it
+// was derived with so the vault can be re-saved under the same header
+#[derive(Clone)]
+struct PasswordKey {
+    key: [u8; 32],
+    kdf: Kdf,
+    salt: Vec<u8>,
+}
 
-// Get a unique machine identifier
-fn get_machine_id() -> String {
-    // Try to get hostname first
-    if let Ok(hostname) = env::var("HOSTNAME") {
-        return hostname;
+impl Drop for PasswordKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
     }
-    
-    // Fallback to computer name on macOS
-    #[cfg(target_os = "macos")]
-    {
-        if let Ok(output) = std::process::Command::new("scutil")
-            .arg("--get")
-            .arg("ComputerName")
-            .output() {
-            if let Ok(name) = String::from_utf8(output.stdout) {
-                return name.trim().to_string();
-            }
-        }
+}
+
+// Master password key for the active vault (set while a password vault is unlocked)
+static PASSWORD_KEY: Lazy<Mutex<Option<PasswordKey>>> = Lazy::new(|| Mutex::new(None));
+
+// Idle period after which an unlocked password-protected vault locks itself
+const AUTO_LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+// Read from the settings file once, since it is checked on every command
+static AUTO_LOCK_TIMEOUT: Lazy<Mutex<Option<Duration>>> = Lazy::new(|| {
+    let minutes = load_settings()
+        .map(|settings| settings.auto_lock_minutes)
+        .unwrap_or(settings::DEFAULT_AUTO_LOCK_MINUTES);
+    Mutex::new(auto_lock_duration(minutes))
+});
+static LAST_ACTIVITY: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+static AUTO_LOCK_WATCHER: Once = Once::new();
+
+// Certificates expiring within this many days are reported as expiring soon
+const DEFAULT_CERTIFICATE_EXPIRY_WARNING_DAYS: u32 = 30;
+
+// Get the machine-specific encryption key (for local files)
+fn get_encryption_key() -> Result<[u8; 32], String> {
+    MACHINE_KEY.clone()
+}
+
+// Derive a password-based key for a new export, returning the KDF and salt to store with it
+fn new_password_encryption_key(password: &str) -> Result<([u8; 32], Kdf, [u8; vault::SALT_LEN]), String> {
+    // New files always use Argon2id, whatever the settings file says
+    let kdf = match load_settings()?.password_kdf {
+        kdf @ Kdf::Argon2id { .. } => kdf,
+        _ => Kdf::default_argon2id(),
+    };
+    let salt = vault::generate_salt();
+    let key = vault::derive_key(&kdf, password, Some(&salt))?;
+    Ok((key, kdf, salt))
+}
+
+// Get the master password key, if the active vault is unlocked
+fn get_password_key() -> Option<PasswordKey> {
+    PASSWORD_KEY.lock().unwrap().clone()
+}
+
+// Set the master password key for the active vault
+fn set_password_key(password_key: PasswordKey) {
+    let mut current = PASSWORD_KEY.lock().unwrap();
+    *current = Some(password_key);
+}
+
+// Forget the master password key
+fn clear_password_key() {
+    let mut current = PASSWORD_KEY.lock().unwrap();
+    *current = None;
+}
+
+// Record vault activity for the auto-lock timer
+fn touch_activity() {
+    let mut last_activity = LAST_ACTIVITY.lock().unwrap();
+    *last_activity = Instant::now();
+}
+
+// Wipe decrypted keys and the master password key from memory
+fn lock() {
+    clear_password_key();
+    clear_cache();
+}
+
+fn auto_lock_duration(minutes: u64) -> Option<Duration> {
+    if minutes == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(minutes.saturating_mul(60)))
     }
-    
-    // Final fallback
-    "unknown-machine".to_string()
 }
 
-// Derive encryption key from password
-fn derive_key_from_password(password: &str) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(password.as_bytes());
-    hasher.update(b"ssh-kim-password-salt");
-    let result = hasher.finalize();
-    let mut key = [0u8; 32];
-    key.copy_from_slice(&result);
-    key
+// Lock the vault if it has been idle for longer than the auto-lock timeout
+fn lock_if_idle() {
+    let timeout = match *AUTO_LOCK_TIMEOUT.lock().unwrap() {
+        Some(timeout) => timeout,
+        None => return,
+    };
+    
+    let idle = LAST_ACTIVITY.lock().unwrap().elapsed();
+    if idle >= timeout && PASSWORD_KEY.lock().unwrap().is_some() {
+        println!("Vault idle for {}s, locking", idle.as_secs());
+        lock();
+    }
 }
 
-// Get the machine-specific encryption key (for local files)
-fn get_encryption_key() -> [u8; 32] {
-    *MACHINE_KEY
+// Start the background thread that enforces the auto-lock timeout even
+// when no commands are being called
+fn start_auto_lock_watcher() {
+    AUTO_LOCK_WATCHER.call_once(|| {
+        thread::spawn(|| loop {
+            thread::sleep(AUTO_LOCK_CHECK_INTERVAL);
+            lock_if_idle();
+        });
+    });
 }
 
-// Get password-based encryption key (for export/import)
-fn get_password_encryption_key(password: &str) -> [u8; 32] {
-    derive_key_from_password(password)
+// How the active vault is encrypted when it is saved
+enum VaultKey {
+    Machine,
+    Password(PasswordKey),
 }
 
-// Set password-based encryption key
-fn set_password_key(password: &str) {
-    let key = derive_key_from_password(password);
-    if let Ok(mut password_key) = PASSWORD_KEY.lock() {
-        *password_key = Some(key);
+// Work out which key the active vault must be saved with. A password-protected
+// vault is never silently re-encrypted with the machine key while it is locked.
+fn current_vault_key() -> Result<VaultKey, String> {
+    if let Some(password_key) = get_password_key() {
+        return Ok(VaultKey::Password(password_key));
+    }
+    
+    if read_vault_key_source()? == Some(KeySource::Password) {
+        return Err(VAULT_LOCKED_ERROR.to_string());
     }
+    
+    Ok(VaultKey::Machine)
 }
 
-// Clear password-based encryption key (fall back to machine-specific)
-fn clear_password_key() {
-    if let Ok(mut password_key) = PASSWORD_KEY.lock() {
-        *password_key = None;
+// Read the key source recorded in the active vault's header, if it has one
+fn read_vault_key_source() -> Result<Option<KeySource>, String> {
+    let keys_file = get_keys_file_path()?;
+    if !keys_file.exists() {
+        return Ok(None);
     }
+    
+    let encrypted_content = fs::read(&keys_file)
+        .map_err(|e| format!("Failed to read keys file: {}", e))?;
+    
+    Ok(vault::parse(&encrypted_content)?.key_source())
 }
 
 // In-memory cache for SSH keys
 static KEYS_CACHE: Lazy<Mutex<Option<Vec<SshKey>>>> = Lazy::new(|| Mutex::new(None));
 
-// Global state for custom file path
+// Global state for custom file path. Only held long enough to read or set
+// it: KEYS_CACHE is held while the path is resolved, so holding this while
+// taking the cache would deadlock.
 static CUSTOM_FILE_PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
 
+// How long a save waits for another process to release the keys file lock
+const VAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The keys file as this process last read or wrote it, so that changes made
+// by another instance or a sync client are detected before they are overwritten
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ObservedVault {
+    // Not loaded since the path changed; the next save doesn't check
+    Unobserved,
+    // The file didn't exist
+    Missing,
+    // SHA-256 of the file content
+    Content([u8; 32]),
+    // The file changed under us; the cache must be reloaded before saving
+    Stale,
+}
+
+static OBSERVED_VAULT: Lazy<Mutex<ObservedVault>> = Lazy::new(|| Mutex::new(ObservedVault::Unobserved));
+
+fn set_observed_vault(observed: ObservedVault) {
+    let mut current = OBSERVED_VAULT.lock().unwrap();
+    *current = observed;
+}
+
+// Remember the content of the keys file as just read or written
+fn observe_vault_content(content: &[u8]) {
+    set_observed_vault(ObservedVault::Content(Sha256::digest(content).into()));
+}
+
+// Hash the keys file as it is on disk now
+fn read_observed_vault(keys_file: &Path) -> Result<ObservedVault, String> {
+    match fs::read(keys_file) {
+        Ok(content) => Ok(ObservedVault::Content(Sha256::digest(&content).into())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ObservedVault::Missing),
+        Err(e) => Err(format!("Failed to read keys file: {}", e)),
+    }
+}
+
+// Path of the advisory lock file guarding writes to a keys file
+fn get_lock_file_path(keys_file: &Path) -> PathBuf {
+    let mut lock_file = keys_file.as_os_str().to_owned();
+    lock_file.push(".lock");
+    PathBuf::from(lock_file)
+}
+
 // Get keys from cache or load from file
 fn get_cached_keys() -> Result<Vec<SshKey>, String> {
+    // Must run before taking the cache lock, since locking clears the cache
+    lock_if_idle();
+    touch_activity();
+    
     let mut cache = KEYS_CACHE.lock().unwrap();
     if let Some(cached_keys) = &*cache {
-        return Ok(cached_keys.clone());
+        if *OBSERVED_VAULT.lock().unwrap() != ObservedVault::Stale {
+            return Ok(cached_keys.clone());
+        }
     }
     
     // Load from file if not in cache
@@ -137,29 +287,107 @@ fn update_cache_and_save(keys: Vec<SshKey>) -> Result<(), String> {
 fn clear_cache() {
     let mut cache = KEYS_CACHE.lock().unwrap();
     *cache = None;
+    set_observed_vault(ObservedVault::Unobserved);
+}
+
+// How the active vault changed when it was modified outside this process
+#[derive(Serialize, Clone)]
+pub struct VaultChange {
+    pub path: String,
+    pub added: Vec<SshKey>,
+    pub removed: Vec<SshKey>,
+    pub modified: Vec<SshKey>,
+    // Set when the new file couldn't be loaded, e.g. it was re-keyed elsewhere
+    pub error: Option<String>,
+}
+
+// Called by the file watcher when the keys file may have changed. Returns
+// None if the file is as this process last read or wrote it (including our
+// own saves); otherwise drops the cache, reloads the file if keys were
+// loaded, and reports which keys were added, removed or modified.
+pub(crate) fn reload_vault_if_changed() -> Option<VaultChange> {
+    let keys_file = get_keys_file_path().ok()?;
+    let on_disk = read_observed_vault(&keys_file).ok()?;
+    
+    let mut cache = KEYS_CACHE.lock().unwrap();
+    if *OBSERVED_VAULT.lock().unwrap() == on_disk {
+        return None;
+    }
+    
+    let mut change = VaultChange {
+        path: keys_file.to_string_lossy().to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        error: None,
+    };
+    
+    // Saves are refused until the new file has been loaded
+    set_observed_vault(ObservedVault::Stale);
+    
+    // Nothing was loaded (or the vault is locked), so there is nothing to diff
+    let previous = match cache.take() {
+        Some(previous) => previous,
+        None => return Some(change),
+    };
+    
+    match load_keys() {
+        Ok(current) => {
+            for key in &current {
+                match previous.iter().find(|k| k.id == key.id) {
+                    None => change.added.push(without_private_key(key.clone())),
+                    Some(old) if old != key => change.modified.push(without_private_key(key.clone())),
+                    Some(_) => {}
+                }
+            }
+            change.removed = previous.into_iter()
+                .filter(|old| !current.iter().any(|k| k.id == old.id))
+                .map(without_private_key)
+                .collect();
+            *cache = Some(current);
+        }
+        Err(e) => change.error = Some(e),
+    }
+    
+    Some(change)
 }
 
 // Get the path to the encrypted SSH keys file
-fn get_keys_file_path() -> Result<PathBuf, String> {
+pub(crate) fn get_keys_file_path() -> Result<PathBuf, String> {
     // Check if custom path is set
-    let custom_path = CUSTOM_FILE_PATH.lock().unwrap();
-    if let Some(path) = &*custom_path {
-        return Ok(path.clone());
+    if let Some(path) = CUSTOM_FILE_PATH.lock().unwrap().clone() {
+        return Ok(path);
     }
     
     // Default to user's home directory
-    let home_dir = get_home_dir()?;
-    let ssh_kim_dir = home_dir.join(".ssh-kim");
+    let ssh_kim_dir = get_ssh_kim_dir()?;
     
     // Create .ssh-kim directory if it doesn't exist
-    if !ssh_kim_dir.exists() {
-        fs::create_dir_all(&ssh_kim_dir)
-            .map_err(|e| format!("Failed to create .ssh-kim directory: {}", e))?;
-    }
+    fs_util::create_private_dir(&ssh_kim_dir)
+        .map_err(|e| format!("Failed to create .ssh-kim directory: {}", e))?;
     
     Ok(ssh_kim_dir.join("keys.enc"))
 }
 
+// Get the app data directory (~/.ssh-kim)
+fn get_ssh_kim_dir() -> Result<PathBuf, String> {
+    Ok(get_home_dir()?.join(".ssh-kim"))
+}
+
+// Settings are read from disk each time, so they follow edits by other instances
+fn load_settings() -> Result<Settings, String> {
+    Ok(settings::load(&get_ssh_kim_dir()?.join("settings.json")))
+}
+
+fn save_settings(settings: &Settings) -> Result<(), String> {
+    settings::save(&get_ssh_kim_dir()?.join("settings.json"), settings)
+}
+
+// Get the directory rolling backups are kept in (~/.ssh-kim/backups)
+fn get_backups_dir() -> Result<PathBuf, String> {
+    Ok(get_ssh_kim_dir()?.join("backups"))
+}
+
 // Get user's home directory
 fn get_home_dir() -> Result<PathBuf, String> {
     env::var("HOME")
@@ -168,63 +396,79 @@ fn get_home_dir() -> Result<PathBuf, String> {
         .map_err(|_| "Failed to get home directory".to_string())
 }
 
-// Encrypt data
-fn encrypt_data(data: &str) -> Result<String, String> {
-    let cipher = Aes256::new_from_slice(&get_encryption_key())
-        .map_err(|e| format!("Failed to create cipher: {}", e))?;
-    
-    let mut rng = rand::thread_rng();
-    let iv: [u8; 16] = rng.gen();
-    
-    // Pad data to 16-byte blocks
-    let mut padded_data = data.as_bytes().to_vec();
-    let padding = 16 - (padded_data.len() % 16);
-    padded_data.extend(std::iter::repeat(padding as u8).take(padding));
-    
-    let mut encrypted = Vec::new();
-    encrypted.extend_from_slice(&iv);
+// Returned by every command that needs keys while the vault is locked. The
+// frontend matches on the VAULT_LOCKED prefix to show the unlock prompt.
+pub const VAULT_LOCKED_ERROR: &str = "VAULT_LOCKED: The keys file is protected with a master password and is locked. Unlock it to continue.";
+// Returned when the keys file was changed by another program since it was
+// loaded. Nothing is written; the next read reloads the file from disk.
+pub const VAULT_CONFLICT_ERROR: &str = "VAULT_CONFLICT: The keys file was changed by another program since it was loaded. Your change was not saved; reload the keys and try again.";
+const PASSWORD_PROTECTED_EXPORT_ERROR: &str = "This is a password-protected export. Use 'Import with Password Protection' instead.";
+const MACHINE_KEYED_FILE_ERROR: &str = "This file is not password-protected. It is encrypted with a machine-specific key and can only be opened with Load or Merge on the machine that created it.";
+
+// Encrypt data with the machine-specific key
+fn encrypt_data(data: &str) -> Result<Vec<u8>, String> {
+    vault::seal(&get_encryption_key()?, KeySource::Machine, Kdf::None, None, data)
+}
+
+// Decrypt with the machine-specific key, falling back to the keys older
+// versions derived from the hostname. The flag is set when a legacy key was
+// needed, meaning the file should be re-keyed.
+fn open_with_machine_key(file: &VaultFile) -> Result<(String, bool), String> {
+    let error = match vault::open(&get_encryption_key()?, file) {
+        Ok(content) => return Ok((content, false)),
+        Err(e) => e,
+    };
     
-    for chunk in padded_data.chunks(16) {
-        let mut block = GenericArray::clone_from_slice(chunk);
-        cipher.encrypt_block(&mut block);
-        encrypted.extend_from_slice(block.as_slice());
+    for machine_name in machine::legacy_machine_names() {
+        if let Ok(content) = vault::open(&machine::derive_legacy_machine_key(&machine_name), file) {
+            println!("Decrypted keys file with the legacy key for machine name {:?}", machine_name);
+            return Ok((content, true));
+        }
     }
     
-    Ok(general_purpose::STANDARD.encode(encrypted))
+    Err(error)
 }
 
-// Decrypt data
-fn decrypt_data(encrypted_data: &str) -> Result<String, String> {
-    let cipher = Aes256::new_from_slice(&get_encryption_key())
-        .map_err(|e| format!("Failed to create cipher: {}", e))?;
-    
-    let encrypted_bytes = general_purpose::STANDARD.decode(encrypted_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-    
-    if encrypted_bytes.len() < 16 {
-        return Err("Invalid encrypted data".to_string());
+// Decrypt a file that should be encrypted with the machine-specific key
+fn decrypt_machine_file(file: &VaultFile) -> Result<(String, bool), String> {
+    match file.key_source() {
+        Some(KeySource::Password) => Err(PASSWORD_PROTECTED_EXPORT_ERROR.to_string()),
+        Some(KeySource::Machine) => open_with_machine_key(file)
+            .map_err(|e| format!("{}. Machine-keyed files can only be opened on the machine that created them. If this machine was renamed, use 'Recover with previous machine name'.", e)),
+        // Headerless files don't say how they were encrypted, so the machine key is a guess
+        None => open_with_machine_key(file)
+            .map_err(|_| "Failed to decrypt file. The file may be password-protected or corrupted. Try using 'Import with Password Protection' instead.".to_string()),
     }
-    
-    let _iv = &encrypted_bytes[..16];
-    let data = &encrypted_bytes[16..];
-    
-    let mut decrypted = Vec::new();
-    
-    for chunk in data.chunks(16) {
-        let mut block = GenericArray::clone_from_slice(chunk);
-        cipher.decrypt_block(&mut block);
-        decrypted.extend_from_slice(block.as_slice());
+}
+
+// Decrypt the active vault with whichever key its header asks for
+fn decrypt_vault_file(file: &VaultFile) -> Result<(String, bool), String> {
+    if file.key_source() != Some(KeySource::Password) {
+        return decrypt_machine_file(file);
     }
     
-    // Remove padding
-    if let Some(&padding) = decrypted.last() {
-        if padding <= 16 && padding > 0 {
-            decrypted.truncate(decrypted.len() - padding as usize);
+    let password_key = get_password_key().ok_or(VAULT_LOCKED_ERROR)?;
+    Ok((vault::open(&password_key.key, file)?, false))
+}
+
+// Decrypt a password-protected export
+fn decrypt_password_file(file: &VaultFile, password: &str) -> Result<String, String> {
+    let password_key = match file.header() {
+        Some(header) if header.key_source == KeySource::Machine => {
+            return Err(MACHINE_KEYED_FILE_ERROR.to_string());
         }
-    }
+        Some(header) => vault::derive_key(&header.kdf, password, header.salt_bytes()?.as_deref())?,
+        // Headerless exports always used the legacy password hash
+        None => vault::derive_legacy_key(password),
+    };
     
-    String::from_utf8(decrypted)
-        .map_err(|e| format!("Failed to convert to string: {}", e))
+    vault::open(&password_key, file).map_err(|e| {
+        if e == vault::AUTHENTICATION_FAILED {
+            "Incorrect password, or the file is corrupted or has been tampered with".to_string()
+        } else {
+            e
+        }
+    })
 }
 
 // Load SSH keys from encrypted file
@@ -232,43 +476,233 @@ fn load_keys() -> Result<Vec<SshKey>, String> {
     let keys_file = get_keys_file_path()?;
     
     if !keys_file.exists() {
+        set_observed_vault(ObservedVault::Missing);
         return Ok(Vec::new());
     }
     
-    let encrypted_content = fs::read_to_string(&keys_file)
+    let encrypted_content = fs::read(&keys_file)
         .map_err(|e| format!("Failed to read keys file: {}", e))?;
     
-    let decrypted_content = decrypt_data(&encrypted_content)?;
+    let file = vault::parse(&encrypted_content)?;
+    let (decrypted_content, needs_rekey) = decrypt_vault_file(&file)?;
+    observe_vault_content(&encrypted_content);
     
-    serde_json::from_str(&decrypted_content)
-        .map_err(|e| format!("Failed to parse keys file: {}", e))
+    let keys: Vec<SshKey> = parse_keys(&decrypted_content)
+        .map_err(|e| format!("Failed to parse keys file: {}", e))?;
+    
+    // Rewrite files from older versions in the current format and key
+    if !file.is_current_version() || needs_rekey {
+        println!("Migrating keys file to the current vault format and machine key");
+        save_keys(&keys)?;
+    }
+    
+    Ok(keys)
 }
 
 // Save SSH keys to encrypted file
 fn save_keys(keys: &[SshKey]) -> Result<(), String> {
+    let vault_key = current_vault_key()?;
+    save_keys_with(keys, &vault_key)
+}
+
+// Save SSH keys to encrypted file with an explicit key
+fn save_keys_with(keys: &[SshKey], vault_key: &VaultKey) -> Result<(), String> {
     let keys_file = get_keys_file_path()?;
     
     let content = serde_json::to_string_pretty(keys)
         .map_err(|e| format!("Failed to serialize keys: {}", e))?;
     
-    let encrypted_content = encrypt_data(&content)?;
+    let encrypted_content = match vault_key {
+        VaultKey::Machine => encrypt_data(&content)?,
+        VaultKey::Password(password_key) => vault::seal(
+            &password_key.key,
+            KeySource::Password,
+            password_key.kdf.clone(),
+            Some(&password_key.salt),
+            &content,
+        )?,
+    };
+    
+    // Hold the lock from the change check until the new file is in place so
+    // another instance can't write in between
+    let _lock = fs_util::lock_exclusive(&get_lock_file_path(&keys_file), VAULT_LOCK_TIMEOUT)
+        .map_err(|e| format!("Failed to lock keys file: {}", e))?;
+    
+    let observed = *OBSERVED_VAULT.lock().unwrap();
+    if observed == ObservedVault::Stale
+        || (observed != ObservedVault::Unobserved && read_observed_vault(&keys_file)? != observed) {
+        println!("Keys file changed on disk since it was loaded, refusing to overwrite it");
+        set_observed_vault(ObservedVault::Stale);
+        return Err(VAULT_CONFLICT_ERROR.to_string());
+    }
+    
+    // Keep a copy of the previous version before overwriting it
+    let backup_limit = load_settings()?.backup_limit;
+    backup::create_backup(&get_backups_dir()?, &keys_file, backup_limit)?;
+    
+    fs_util::write_atomic(&keys_file, &encrypted_content)
+        .map_err(|e| format!("Failed to write keys file: {}", e))?;
+    observe_vault_content(&encrypted_content);
     
-    fs::write(&keys_file, encrypted_content)
-        .map_err(|e| format!("Failed to write keys file: {}", e))
+    Ok(())
 }
 
-// Detect SSH key type from key content
-fn detect_key_type(key_content: &str) -> String {
-    if key_content.contains("ssh-rsa") {
-        "rsa".to_string()
-    } else if key_content.contains("ssh-dss") {
-        "dsa".to_string()
-    } else if key_content.contains("ecdsa-") {
-        "ecdsa".to_string()
-    } else if key_content.contains("ssh-ed25519") {
-        "ed25519".to_string()
-    } else {
-        "unknown".to_string()
+// Decrypt a backup, which may be machine-keyed or protected with the master
+// password that was in use when it was taken
+fn decrypt_backup(encrypted_content: &[u8], password: Option<&str>) -> Result<Vec<SshKey>, String> {
+    let file = vault::parse(encrypted_content)?;
+    
+    let decrypted_content = match (file.key_source(), password) {
+        (Some(KeySource::Password), Some(password)) => decrypt_password_file(&file, password)?,
+        (Some(KeySource::Password), None) => decrypt_vault_file(&file)
+            .map(|(content, _)| content)
+            .map_err(|_| "This backup is protected with a master password. Enter the password that was in use when it was taken.".to_string())?,
+        _ => decrypt_machine_file(&file)?.0,
+    };
+    
+    parse_keys(&decrypted_content)
+        .map_err(|e| format!("Failed to parse backup: {}", e))
+}
+
+// Set a key's type and parsed details from its content
+fn apply_key_details(key: &mut SshKey, parsed: public_key::PublicKey) {
+    key.fingerprint = parsed.fingerprint_sha256();
+    key.fingerprint_md5 = parsed.fingerprint_md5();
+    key.key_type = parsed.key_type;
+    key.algorithm = parsed.algorithm;
+    key.bits = parsed.bits;
+    key.curve = parsed.curve;
+    key.comment = parsed.comment;
+    key.certificate = parsed.certificate;
+    // Flags come from the private key, so keep any already recorded
+    let flags = key.security_key.as_ref().and_then(|sk| sk.flags);
+    key.security_key = parsed.security_key;
+    if let (Some(security_key), Some(flags)) = (key.security_key.as_mut(), flags) {
+        security_key.set_flags(flags);
+    }
+}
+
+// A new store entry for a parsed public key
+fn new_key_entry(name: String, tag: Option<String>, key_content: &str, parsed: public_key::PublicKey) -> SshKey {
+    let now = Utc::now();
+    let mut key = SshKey {
+        id: Uuid::new_v4().to_string(),
+        name,
+        tag,
+        key: key_content.trim().to_string(),
+        key_type: String::new(),
+        algorithm: String::new(),
+        bits: None,
+        curve: None,
+        comment: None,
+        fingerprint: String::new(),
+        fingerprint_md5: String::new(),
+        certificate: None,
+        security_key: None,
+        private_key: None,
+        has_private_key: false,
+        private_key_encrypted: false,
+        is_ca: false,
+        revoked: None,
+        revoked_serials: Vec::new(),
+        created: now,
+        last_modified: now,
+    };
+    apply_key_details(&mut key, parsed);
+    key
+}
+
+// Split incoming keys into keys new to the store and duplicates, either of a
+// stored key or of an earlier incoming key
+fn partition_duplicates(current: &[SshKey], incoming: Vec<SshKey>) -> (Vec<SshKey>, Vec<DuplicateKey>) {
+    let mut new_keys: Vec<SshKey> = Vec::new();
+    let mut duplicates = Vec::new();
+    
+    for key in incoming {
+        let duplicate = merge::find_duplicate(current, &key)
+            .or_else(|| merge::find_duplicate(&new_keys, &key))
+            .map(|existing| DuplicateKey {
+                name: key.name.clone(),
+                fingerprint: key.fingerprint.clone(),
+                existing_id: existing.id.clone(),
+                existing_name: existing.name.clone(),
+            });
+        
+        match duplicate {
+            Some(duplicate) => duplicates.push(duplicate),
+            None => new_keys.push(key),
+        }
+    }
+    
+    (new_keys, duplicates)
+}
+
+// Private keys never leave the backend except through reveal_private_key and
+// export_private_key
+fn without_private_key(key: SshKey) -> SshKey {
+    SshKey { private_key: None, ..key }
+}
+
+fn without_private_keys(keys: Vec<SshKey>) -> Vec<SshKey> {
+    keys.into_iter().map(without_private_key).collect()
+}
+
+// Parse a decrypted keys file, filling in details older versions didn't record
+fn parse_keys(content: &str) -> Result<Vec<SshKey>, serde_json::Error> {
+    let mut keys: Vec<SshKey> = serde_json::from_str(content)?;
+    backfill_key_details(&mut keys);
+    Ok(keys)
+}
+
+// Fill in parsed details for keys saved before they were recorded
+fn backfill_key_details(keys: &mut [SshKey]) {
+    let missing_details = |k: &SshKey| k.algorithm.is_empty()
+        || k.fingerprint.is_empty()
+        || (k.key_type.ends_with("-cert") && k.certificate.as_ref().is_none_or(|c| c.ca_key.is_empty()))
+        || (k.algorithm.starts_with("sk-") && k.security_key.is_none());
+    
+    // Keys stored in RFC 4716 or PEM form are converted to OpenSSH lines
+    for key in keys.iter_mut().filter(|k| missing_details(k)) {
+        if let Ok(line) = key_format::to_openssh(&key.key) {
+            if let Ok(parsed) = public_key::parse(&line) {
+                key.key = line;
+                apply_key_details(key, parsed);
+            }
+        }
+    }
+    
+    for key in keys.iter_mut().filter(|k| k.private_key.is_some() && !k.has_private_key) {
+        key.has_private_key = true;
+        key.private_key_encrypted = key.private_key.as_deref()
+            .and_then(|text| private_key::parse(text).ok())
+            .is_some_and(|parsed| parsed.is_encrypted());
+    }
+    
+    for key in keys.iter_mut().filter(|k| k.security_key.as_ref().is_some_and(|sk| sk.flags.is_none())) {
+        if let Some(parsed) = key.private_key.as_deref().and_then(|text| private_key::parse(text).ok()) {
+            record_security_key_flags(key, &parsed);
+        }
+    }
+}
+
+// Store or clear an entry's private key, keeping the flags the frontend sees in step
+fn set_private_key(key: &mut SshKey, private_key: Option<String>) -> Result<(), String> {
+    key.private_key_encrypted = false;
+    if let Some(text) = &private_key {
+        let parsed = private_key::parse(text)?;
+        record_security_key_flags(key, &parsed);
+        key.private_key_encrypted = parsed.is_encrypted();
+    }
+    key.has_private_key = private_key.is_some();
+    key.private_key = private_key;
+    Ok(())
+}
+
+// Record a security key's authenticator flags, which only an unencrypted
+// private key shows
+fn record_security_key_flags(key: &mut SshKey, parsed: &PrivateKey) {
+    if let (Some(security_key), Some(flags)) = (key.security_key.as_mut(), private_key::security_key_flags(parsed)) {
+        security_key.set_flags(flags);
     }
 }
 
@@ -282,7 +716,7 @@ fn get_default_ssh_dir() -> Result<PathBuf, String> {
 }
 
 // Get common SSH public key locations
-fn get_common_ssh_locations() -> Vec<PathBuf> {
+pub(crate) fn get_common_ssh_locations() -> Vec<PathBuf> {
     let mut locations = Vec::new();
     
     // Default SSH directory (contains public keys)
@@ -302,7 +736,7 @@ fn get_common_ssh_locations() -> Vec<PathBuf> {
 }
 
 // Scan directory for SSH public keys
-fn scan_directory_for_keys(dir_path: &std::path::Path) -> Result<Vec<String>, String> {
+pub(crate) fn scan_directory_for_keys(dir_path: &std::path::Path) -> Result<Vec<String>, String> {
     if !dir_path.exists() || !dir_path.is_dir() {
         return Ok(Vec::new());
     }
@@ -319,11 +753,20 @@ fn scan_directory_for_keys(dir_path: &std::path::Path) -> Result<Vec<String>, St
                 .and_then(|n| n.to_str())
                 .unwrap_or("");
             
-            // Only look for public key files (.pub extension)
+            // PuTTY keys (.ppk) always carry their public key in the clear
+            if file_name.ends_with(".ppk") {
+                if let Ok(content) = fs::read_to_string(&path) {
+                    if ppk::parse(&content).is_ok() {
+                        keys.push(path.to_string_lossy().to_string());
+                    }
+                }
+            }
+            
+            // Public key files (.pub extension)
             if file_name.ends_with(".pub") {
                 if let Ok(content) = fs::read_to_string(&path) {
-                    // Verify it's actually a public key by checking for SSH key format
-                    if content.contains("ssh-") && content.lines().count() == 1 {
+                    // Verify it's actually a well-formed public key, in any supported format
+                    if key_format::to_openssh(&content).and_then(|line| public_key::parse(&line)).is_ok() {
                         keys.push(path.to_string_lossy().to_string());
                     }
                 }
@@ -336,36 +779,28 @@ fn scan_directory_for_keys(dir_path: &std::path::Path) -> Result<Vec<String>, St
 
 #[tauri::command]
 pub fn get_ssh_keys() -> Result<Vec<SshKey>, String> {
-    get_cached_keys()
+    get_cached_keys().map(without_private_keys)
 }
 
 #[tauri::command]
 pub fn add_ssh_key(name: String, tag: Option<String>, key_content: String) -> Result<SshKey, String> {
     let mut keys = get_cached_keys()?;
     
-    // Check for duplicate keys by comparing the key content
-    if keys.iter().any(|k| k.key.trim() == key_content.trim()) {
-        return Err("A key with this content already exists".to_string());
+    let key_content = key_format::to_openssh(&key_content)?;
+    let parsed = public_key::parse(&key_content)?;
+    
+    let new_key = new_key_entry(name, tag, &key_content, parsed);
+    
+    // Check for duplicate keys by comparing the key blob, so a different comment doesn't count
+    if let Some(existing) = keys.iter().find(|k| merge::is_same_key(k, &new_key)) {
+        return Err(format!("A key with this content already exists: '{}' ({})", existing.name, new_key.fingerprint));
     }
     
     // Check for duplicate names
-    if keys.iter().any(|k| k.name.trim().to_lowercase() == name.trim().to_lowercase()) {
+    if keys.iter().any(|k| k.name.trim().to_lowercase() == new_key.name.trim().to_lowercase()) {
         return Err("A key with this name already exists".to_string());
     }
     
-    let now = Utc::now();
-    let trimmed_key_content = key_content.trim().to_string();
-    let key_type = detect_key_type(&trimmed_key_content);
-    let new_key = SshKey {
-        id: Uuid::new_v4().to_string(),
-        name,
-        tag,
-        key: trimmed_key_content,
-        key_type,
-        created: now,
-        last_modified: now,
-    };
-    
     keys.push(new_key.clone());
     update_cache_and_save(keys)?;
     
@@ -386,10 +821,24 @@ pub fn update_ssh_key(id: String, update: SshKeyUpdate) -> Result<SshKey, String
         }
     }
     
+    let update_key = update.key.as_deref().map(key_format::to_openssh).transpose()?;
+    let parsed = update_key.as_deref().map(public_key::parse).transpose()?;
+    
+    // A stored private key only fits its own public key
+    if let Some(parsed) = &parsed {
+        if keys[key_index].private_key.is_some() && parsed.fingerprint_sha256() != keys[key_index].fingerprint {
+            return Err("This key has a stored private key, so its public key can't be replaced".to_string());
+        }
+    }
+    
     // Check for duplicate key content (excluding the current key)
-    if let Some(key_content) = &update.key {
-        if keys.iter().any(|k| k.id != id && k.key.trim() == key_content.trim()) {
-            return Err("A key with this content already exists".to_string());
+    if let (Some(parsed), Some(key_content)) = (&parsed, &update_key) {
+        let mut candidate = keys[key_index].clone();
+        candidate.key = key_content.trim().to_string();
+        apply_key_details(&mut candidate, parsed.clone());
+        
+        if let Some(existing) = keys.iter().find(|k| k.id != id && merge::is_same_key(k, &candidate)) {
+            return Err(format!("A key with this content already exists: '{}' ({})", existing.name, candidate.fingerprint));
         }
     }
     
@@ -401,10 +850,12 @@ pub fn update_ssh_key(id: String, update: SshKeyUpdate) -> Result<SshKey, String
         keys[key_index].tag = Some(tag);
     }
     
-    if let Some(key_content) = update.key {
-        let trimmed_key_content = key_content.trim().to_string();
-        keys[key_index].key = trimmed_key_content;
-        keys[key_index].key_type = detect_key_type(&keys[key_index].key);
+    if let Some(key_content) = update_key {
+        keys[key_index].key = key_content;
+    }
+    
+    if let Some(parsed) = parsed {
+        apply_key_details(&mut keys[key_index], parsed);
     }
     
     keys[key_index].last_modified = Utc::now();
@@ -412,7 +863,7 @@ pub fn update_ssh_key(id: String, update: SshKeyUpdate) -> Result<SshKey, String
     let updated_key = keys[key_index].clone();
     update_cache_and_save(keys)?;
     
-    Ok(updated_key)
+    Ok(without_private_key(updated_key))
 }
 
 #[tauri::command]
@@ -453,21 +904,56 @@ pub fn scan_ssh_locations() -> Result<Vec<SshKeyLocation>, String> {
             Vec::new()
         };
         
+        let mismatched_keys = keys.iter()
+            .filter(|path| check_key_pair(std::path::Path::new(path)) == Some(false))
+            .cloned()
+            .collect();
+        
         results.push(SshKeyLocation {
             path: location.to_string_lossy().to_string(),
             exists,
             keys,
+            mismatched_keys,
         });
     }
     
     Ok(results)
 }
 
+// Read a key file to add. For a private key only its public key is returned,
+// so private key material never ends up in the key content.
 #[tauri::command]
-pub fn read_ssh_key_file(file_path: String) -> Result<String, String> {
-    fs::read_to_string(&file_path)
-        .map(|content| content.trim().to_string())
-        .map_err(|e| format!("Failed to read file {}: {}", file_path, e))
+pub fn read_ssh_key_file(file_path: String) -> Result<SshKeyFile, String> {
+    let content = fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read file {}: {}", file_path, e))?;
+    
+    if let Some(format) = private_key::detect_format(&content) {
+        return Ok(SshKeyFile {
+            content: private_key::derive_public_key(&content)?,
+            private_key_format: Some(format),
+            matches_private_key: None,
+        });
+    }
+    
+    Ok(SshKeyFile {
+        content: key_format::to_openssh(&content)?,
+        private_key_format: None,
+        matches_private_key: check_key_pair(std::path::Path::new(&file_path)),
+    })
+}
+
+// Whether a `.pub` file holds the public key of the private key next to it
+// (`id_ed25519` for `id_ed25519.pub`). None if there is no private key, or
+// either file can't be read, or the private key is an encrypted PEM key.
+fn check_key_pair(public_path: &std::path::Path) -> Option<bool> {
+    let private_path = public_path.to_str()?.strip_suffix(".pub")?;
+    let private_text = fs::read_to_string(private_path).ok()?;
+    private_key::detect_format(&private_text)?;
+    
+    let public_text = key_format::to_openssh(&fs::read_to_string(public_path).ok()?).ok()?;
+    let public = public_key::parse(&public_text).ok()?;
+    let derived = public_key::parse(&private_key::derive_public_key(&private_text).ok()?).ok()?;
+    Some(public.blob == derived.blob)
 }
 
 #[tauri::command]
@@ -518,7 +1004,7 @@ pub fn force_reload_keys() -> Result<Vec<SshKey>, String> {
     clear_cache();
     let keys = get_cached_keys()?;
     println!("Reloaded {} keys", keys.len());
-    Ok(keys)
+    Ok(without_private_keys(keys))
 }
 
 #[tauri::command]
@@ -539,11 +1025,11 @@ pub fn set_custom_keys_file_path(file_path: String) -> Result<(), String> {
     }
     
     // Set the custom path
-    let mut custom_path = CUSTOM_FILE_PATH.lock().unwrap();
-    *custom_path = Some(path);
+    *CUSTOM_FILE_PATH.lock().unwrap() = Some(path);
     
-    // Clear cache to force reload from new location
+    // Clear cache and master password key to force reload from new location
     clear_cache();
+    clear_password_key();
     
     Ok(())
 }
@@ -570,7 +1056,7 @@ pub fn load_keys_from_file(file_path: String) -> Result<Vec<SshKey>, String> {
     
     // Read and decrypt the file
     println!("🔍 load_keys_from_file: Reading file content...");
-    let encrypted_content = fs::read_to_string(&path)
+    let encrypted_content = fs::read(&path)
         .map_err(|e| {
             println!("❌ load_keys_from_file: Failed to read file {}: {}", file_path, e);
             format!("Failed to read file: {}", e)
@@ -578,7 +1064,9 @@ pub fn load_keys_from_file(file_path: String) -> Result<Vec<SshKey>, String> {
     println!("✅ load_keys_from_file: Successfully read file, content length: {}", encrypted_content.len());
     
     println!("🔍 load_keys_from_file: Attempting to decrypt...");
-    let decrypted_content = decrypt_data(&encrypted_content)
+    let decrypted_content = vault::parse(&encrypted_content)
+        .and_then(|file| decrypt_machine_file(&file))
+        .map(|(content, _)| content)
         .map_err(|e| {
             println!("❌ load_keys_from_file: Failed to decrypt file {}: {}", file_path, e);
             format!("Failed to decrypt: {}", e)
@@ -586,7 +1074,7 @@ pub fn load_keys_from_file(file_path: String) -> Result<Vec<SshKey>, String> {
     println!("✅ load_keys_from_file: Successfully decrypted file, content length: {}", decrypted_content.len());
     
     println!("🔍 load_keys_from_file: Parsing JSON...");
-    let keys: Vec<SshKey> = serde_json::from_str(&decrypted_content)
+    let keys: Vec<SshKey> = parse_keys(&decrypted_content)
         .map_err(|e| {
             println!("❌ load_keys_from_file: Failed to parse JSON from file {}: {}", file_path, e);
             format!("Failed to parse keys file: {}", e)
@@ -595,8 +1083,9 @@ pub fn load_keys_from_file(file_path: String) -> Result<Vec<SshKey>, String> {
     
     // Set this as the current custom path
     println!("🔍 load_keys_from_file: Setting custom path...");
-    let mut custom_path = CUSTOM_FILE_PATH.lock().unwrap();
-    *custom_path = Some(path);
+    *CUSTOM_FILE_PATH.lock().unwrap() = Some(path);
+    clear_password_key();
+    observe_vault_content(&encrypted_content);
     println!("✅ load_keys_from_file: Set custom path");
     
     // Update cache with loaded keys
@@ -606,17 +1095,17 @@ pub fn load_keys_from_file(file_path: String) -> Result<Vec<SshKey>, String> {
     println!("✅ load_keys_from_file: Updated cache");
     
     println!("🎉 load_keys_from_file: Function completed successfully");
-    Ok(keys)
+    Ok(without_private_keys(keys))
 }
 
 #[tauri::command]
 pub fn reset_to_default_path() -> Result<(), String> {
     // Clear custom path
-    let mut custom_path = CUSTOM_FILE_PATH.lock().unwrap();
-    *custom_path = None;
+    *CUSTOM_FILE_PATH.lock().unwrap() = None;
     
-    // Clear cache to force reload from default location
+    // Clear cache and master password key to force reload from default location
     clear_cache();
+    clear_password_key();
     
     Ok(())
 }
@@ -662,7 +1151,7 @@ pub fn export_keys_to_file(file_path: String) -> Result<(), String> {
     
     let encrypted_content = encrypt_data(&content)?;
     
-    fs::write(&path, encrypted_content)
+    fs_util::write_atomic(&path, &encrypted_content)
         .map_err(|e| format!("Failed to write file: {}", e))?;
     
     println!("Successfully exported keys to {}", file_path);
@@ -691,52 +1180,32 @@ pub fn open_save_dialog() -> Option<String> {
 } 
 
 #[tauri::command]
-pub fn merge_keys_from_file(source_file_path: String) -> Result<Vec<SshKey>, String> {
+pub fn merge_keys_from_file(source_file_path: String) -> Result<ImportResult, String> {
     println!("🔍 merge_keys_from_file: Starting merge from source file: {}", source_file_path);
     
-    // Try to load keys from the source file with current encryption
-    let source_keys = match load_keys_from_file(source_file_path.clone()) {
-        Ok(keys) => {
-            println!("🔍 merge_keys_from_file: Successfully loaded {} keys with current encryption", keys.len());
-            keys
-        }
-        Err(e) => {
-            // If current encryption fails, try with machine-specific encryption
-            println!("🔍 merge_keys_from_file: Current encryption failed, trying machine-specific: {}", e);
-            
-            // Temporarily clear password key to use machine-specific encryption
-            clear_password_key();
-            
-            match load_keys_from_file(source_file_path.clone()) {
-                Ok(keys) => {
-                    println!("🔍 merge_keys_from_file: Successfully loaded {} keys with machine-specific encryption", keys.len());
-                    keys
-                }
-                Err(e2) => {
-                    println!("🔍 merge_keys_from_file: Machine-specific encryption also failed: {}", e2);
-                    return Err(format!("Failed to decrypt file. The file may be password-protected or corrupted. Try using 'Import with Password Protection' instead."));
-                }
-            }
-        }
-    };
+    // Read the source file without switching the active keys file to it
+    let encrypted_content = fs::read(&source_file_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    
+    let file = vault::parse(&encrypted_content)?;
+    let (decrypted_content, _) = decrypt_machine_file(&file)
+        .inspect_err(|e| println!("🔍 merge_keys_from_file: Failed to decrypt source file: {}", e))?;
+    
+    let source_keys: Vec<SshKey> = parse_keys(&decrypted_content)
+        .map_err(|e| format!("Failed to parse keys file: {}", e))?;
+    println!("🔍 merge_keys_from_file: Loaded {} keys from source file", source_keys.len());
     
     // Get current keys
     let current_keys = get_cached_keys()?;
     println!("🔍 merge_keys_from_file: Current keys count: {}", current_keys.len());
     
-    // Create a set of existing key IDs to avoid duplicates
-    let existing_ids: std::collections::HashSet<String> = current_keys.iter()
-        .map(|key| key.id.clone())
-        .collect();
-    
-    // Filter out duplicates from source keys
-    let new_keys: Vec<SshKey> = source_keys.into_iter()
-        .filter(|key| !existing_ids.contains(&key.id))
-        .collect();
+    // Filter out keys already in the store, matched by id or by key
+    let (new_keys, duplicates) = partition_duplicates(&current_keys, source_keys);
     
-    println!("🔍 merge_keys_from_file: Found {} new keys to merge", new_keys.len());
+    println!("🔍 merge_keys_from_file: Found {} new keys to merge, {} duplicates", new_keys.len(), duplicates.len());
     
     // Combine current keys with new keys
+    let imported_count = new_keys.len();
     let merged_keys = [current_keys, new_keys].concat();
     println!("🔍 merge_keys_from_file: Total keys after merge: {}", merged_keys.len());
     
@@ -749,121 +1218,262 @@ pub fn merge_keys_from_file(source_file_path: String) -> Result<Vec<SshKey>, Str
     *cache = Some(merged_keys.clone());
     
     println!("🎉 merge_keys_from_file: Merge completed successfully");
-    Ok(merged_keys)
+    Ok(ImportResult {
+        total_in_store: merged_keys.len(),
+        keys: without_private_keys(merged_keys),
+        imported_count,
+        duplicate_count: duplicates.len(),
+        duplicates,
+    })
 } 
 
-#[tauri::command]
-pub fn set_encryption_password(password: String) -> Result<(), String> {
-    println!("🔍 set_encryption_password: Setting password-based encryption");
-    set_password_key(&password);
-    println!("✅ set_encryption_password: Password-based encryption enabled");
-    Ok(())
+// Read the keys from a keys file or export, using the password if it is password-protected
+fn read_incoming_keys(file_path: &str, password: Option<&str>) -> Result<Vec<SshKey>, String> {
+    let encrypted_content = fs::read(file_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    
+    let file = vault::parse(&encrypted_content)?;
+    let decrypted_content = match password {
+        Some(password) => decrypt_password_file(&file, password)?,
+        None => decrypt_machine_file(&file)?.0,
+    };
+    
+    parse_keys(&decrypted_content)
+        .map_err(|e| format!("Failed to parse keys file: {}", e))
+}
+
+#[derive(Serialize)]
+pub struct MergeResult {
+    pub keys: Vec<SshKey>,
+    pub added_count: usize,
+    pub updated_count: usize,
+    pub unchanged_count: usize,
+    pub total_in_store: usize,
 }
 
+// Dry run of a merge or import: classify each key in the file as new,
+// identical or conflicting, with the resolution that would be applied
 #[tauri::command]
-pub fn clear_encryption_password() -> Result<(), String> {
-    println!("🔍 clear_encryption_password: Clearing password-based encryption");
-    clear_password_key();
-    println!("✅ clear_encryption_password: Fallback to machine-specific encryption");
-    Ok(())
+pub fn preview_merge(file_path: String, password: Option<String>) -> Result<Vec<MergeEntry>, String> {
+    let incoming = read_incoming_keys(&file_path, password.as_deref())?;
+    let current = get_cached_keys()?;
+    
+    Ok(merge::plan(&current, incoming)
+        .into_iter()
+        .map(|entry| MergeEntry {
+            incoming: without_private_key(entry.incoming),
+            local: entry.local.map(without_private_key),
+            ..entry
+        })
+        .collect())
 }
 
+// Merge or import a file, resolving each key as planned by preview_merge
+// unless `resolutions` (keyed by the incoming key's id) says otherwise
 #[tauri::command]
-pub fn export_keys_with_password(file_path: String, password: String) -> Result<(), String> {
-    println!("🔍 export_keys_with_password: Exporting with password protection");
+pub fn apply_merge(file_path: String, password: Option<String>, resolutions: HashMap<String, MergeResolution>) -> Result<MergeResult, String> {
+    let incoming = read_incoming_keys(&file_path, password.as_deref())?;
+    let current = get_cached_keys()?;
     
-    // Get current keys (decrypted from machine-specific encryption)
-    let keys = get_cached_keys()?;
-    println!("🔍 export_keys_with_password: Exporting {} keys", keys.len());
+    let plan = merge::plan(&current, incoming);
+    let (keys, summary) = merge::apply(current, plan, &resolutions);
     
-    // Create parent directory if it doesn't exist
-    let path = PathBuf::from(&file_path);
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
-        }
+    if summary.added > 0 || summary.updated > 0 {
+        update_cache_and_save(keys.clone())?;
     }
     
-    // Serialize keys to JSON
-    let content = serde_json::to_string_pretty(&keys)
-        .map_err(|e| format!("Failed to serialize keys: {}", e))?;
+    println!("Merged {}: {} added, {} updated, {} unchanged", file_path, summary.added, summary.updated, summary.unchanged);
+    Ok(MergeResult {
+        total_in_store: keys.len(),
+        keys: without_private_keys(keys),
+        added_count: summary.added,
+        updated_count: summary.updated,
+        unchanged_count: summary.unchanged,
+    })
+}
+
+#[tauri::command]
+pub fn set_encryption_password(password: String) -> Result<(), String> {
+    println!("🔍 set_encryption_password: Enabling master password for the keys file");
     
-    // Encrypt with password-based encryption
-    let password_key = get_password_encryption_key(&password);
-    let cipher = Aes256::new_from_slice(&password_key)
-        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+    if password.is_empty() {
+        return Err("Password cannot be empty".to_string());
+    }
     
-    let mut rng = rand::thread_rng();
-    let iv: [u8; 16] = rng.gen();
+    // The vault must be readable before it can be re-encrypted
+    let keys = get_cached_keys()?;
     
-    // Pad data to 16-byte blocks
-    let mut padded_data = content.as_bytes().to_vec();
-    let padding = 16 - (padded_data.len() % 16);
-    padded_data.extend(std::iter::repeat(padding as u8).take(padding));
+    let (key, kdf, salt) = new_password_encryption_key(&password)?;
+    let password_key = PasswordKey { key, kdf, salt: salt.to_vec() };
     
-    let mut encrypted = Vec::new();
-    encrypted.extend_from_slice(&iv);
+    save_keys_with(&keys, &VaultKey::Password(password_key.clone()))?;
+    set_password_key(password_key);
+    touch_activity();
+    start_auto_lock_watcher();
     
-    for chunk in padded_data.chunks(16) {
-        let mut block = GenericArray::clone_from_slice(chunk);
-        cipher.encrypt_block(&mut block);
-        encrypted.extend_from_slice(block.as_slice());
-    }
+    println!("✅ set_encryption_password: Keys file re-encrypted with the master password");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn clear_encryption_password() -> Result<(), String> {
+    println!("🔍 clear_encryption_password: Disabling master password for the keys file");
     
-    let encrypted_content = general_purpose::STANDARD.encode(encrypted);
+    // Fails while the vault is locked, so the password must be known to remove it
+    let keys = get_cached_keys()?;
     
-    // Write encrypted content to file
-    fs::write(&path, encrypted_content)
-        .map_err(|e| format!("Failed to write file: {}", e))?;
+    save_keys_with(&keys, &VaultKey::Machine)?;
+    clear_password_key();
     
-    println!("✅ export_keys_with_password: Successfully exported with password protection");
+    println!("✅ clear_encryption_password: Keys file re-encrypted with the machine-specific key");
     Ok(())
 }
 
 #[tauri::command]
-pub fn import_keys_with_password(file_path: String, password: String) -> Result<ImportResult, String> {
-    println!("🔍 import_keys_with_password: Importing with password protection");
+pub fn unlock_vault(password: String) -> Result<Vec<SshKey>, String> {
+    let keys_file = get_keys_file_path()?;
+    let encrypted_content = fs::read(&keys_file)
+        .map_err(|e| format!("Failed to read keys file: {}", e))?;
     
-    // Read encrypted file
-    let encrypted_content = fs::read_to_string(&file_path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let file = vault::parse(&encrypted_content)?;
+    let header = match file.header() {
+        Some(header) if header.key_source == KeySource::Password => header,
+        _ => return Err("The keys file is not protected with a master password".to_string()),
+    };
+    
+    let salt = header.salt_bytes()?.unwrap_or_default();
+    let key = vault::derive_key(&header.kdf, &password, Some(&salt))?;
+    let decrypted_content = vault::open(&key, &file)
+        .map_err(|_| "Incorrect master password".to_string())?;
+    
+    let keys: Vec<SshKey> = parse_keys(&decrypted_content)
+        .map_err(|e| format!("Failed to parse keys file: {}", e))?;
     
-    // Decrypt with password-based encryption
-    let password_key = get_password_encryption_key(&password);
-    let cipher = Aes256::new_from_slice(&password_key)
-        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+    set_password_key(PasswordKey { key, kdf: header.kdf.clone(), salt });
+    observe_vault_content(&encrypted_content);
+    touch_activity();
+    start_auto_lock_watcher();
     
-    let encrypted_bytes = general_purpose::STANDARD.decode(&encrypted_content)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+    let mut cache = KEYS_CACHE.lock().unwrap();
+    *cache = Some(keys.clone());
+    
+    println!("Vault unlocked");
+    Ok(without_private_keys(keys))
+}
+
+// Re-key a machine-keyed vault that was encrypted under the hostname-based key
+// of an older version, e.g. before this machine was renamed
+#[tauri::command]
+pub fn recover_vault_with_machine_name(machine_name: String) -> Result<Vec<SshKey>, String> {
+    let keys_file = get_keys_file_path()?;
+    let encrypted_content = fs::read(&keys_file)
+        .map_err(|e| format!("Failed to read keys file: {}", e))?;
     
-    if encrypted_bytes.len() < 16 {
-        return Err("Invalid encrypted data".to_string());
+    let file = vault::parse(&encrypted_content)?;
+    if file.key_source() == Some(KeySource::Password) {
+        return Err("The keys file is protected with a master password. Use unlock instead.".to_string());
     }
     
-    let _iv = &encrypted_bytes[..16];
-    let data = &encrypted_bytes[16..];
+    observe_vault_content(&encrypted_content);
+    
+    let legacy_key = machine::derive_legacy_machine_key(machine_name.trim());
+    let decrypted_content = vault::open(&legacy_key, &file)
+        .map_err(|_| format!("The keys file was not encrypted on a machine named {:?}", machine_name.trim()))?;
+    
+    let keys: Vec<SshKey> = parse_keys(&decrypted_content)
+        .map_err(|e| format!("Failed to parse keys file: {}", e))?;
+    
+    save_keys_with(&keys, &VaultKey::Machine)?;
     
-    let mut decrypted = Vec::new();
+    let mut cache = KEYS_CACHE.lock().unwrap();
+    *cache = Some(keys.clone());
     
-    for chunk in data.chunks(16) {
-        let mut block = GenericArray::clone_from_slice(chunk);
-        cipher.decrypt_block(&mut block);
-        decrypted.extend_from_slice(block.as_slice());
+    println!("Re-keyed keys file from machine name {:?}", machine_name.trim());
+    Ok(without_private_keys(keys))
+}
+
+#[tauri::command]
+pub fn lock_vault() -> Result<(), String> {
+    if get_password_key().is_none() && read_vault_key_source()? != Some(KeySource::Password) {
+        return Err("The keys file has no master password, so it cannot be locked. Set a master password first.".to_string());
     }
     
-    // Remove padding
-    if let Some(&padding) = decrypted.last() {
-        if padding <= 16 && padding > 0 {
-            decrypted.truncate(decrypted.len() - padding as usize);
+    lock();
+    println!("Vault locked");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn is_vault_locked() -> Result<bool, String> {
+    lock_if_idle();
+    Ok(get_password_key().is_none() && read_vault_key_source()? == Some(KeySource::Password))
+}
+
+#[tauri::command]
+pub fn get_auto_lock_timeout() -> Result<u64, String> {
+    let timeout = AUTO_LOCK_TIMEOUT.lock().unwrap();
+    Ok(timeout.map(|t| t.as_secs() / 60).unwrap_or(0))
+}
+
+// Set the idle period in minutes after which the vault locks itself; 0 disables auto-lock
+#[tauri::command]
+pub fn set_auto_lock_timeout(minutes: u64) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.auto_lock_minutes = minutes;
+    save_settings(&settings)?;
+    
+    *AUTO_LOCK_TIMEOUT.lock().unwrap() = auto_lock_duration(minutes);
+    touch_activity();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn export_keys_with_password(file_path: String, password: String) -> Result<(), String> {
+    println!("🔍 export_keys_with_password: Exporting with password protection");
+    
+    // Get current keys (decrypted from machine-specific encryption)
+    let keys = get_cached_keys()?;
+    println!("🔍 export_keys_with_password: Exporting {} keys", keys.len());
+    
+    // Create parent directory if it doesn't exist
+    let path = PathBuf::from(&file_path);
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
         }
     }
     
-    let decrypted_content = String::from_utf8(decrypted)
-        .map_err(|e| format!("Failed to convert to string: {}", e))?;
+    // Serialize keys to JSON
+    let content = serde_json::to_string_pretty(&keys)
+        .map_err(|e| format!("Failed to serialize keys: {}", e))?;
+    
+    // Encrypt with a key derived from the password and a fresh salt
+    let (password_key, kdf, salt) = new_password_encryption_key(&password)?;
+    let encrypted_content = vault::seal(&password_key, KeySource::Password, kdf, Some(&salt), &content)?;
+    
+    // Write encrypted content to file
+    fs_util::write_atomic(&path, &encrypted_content)
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    
+    println!("✅ export_keys_with_password: Successfully exported with password protection");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn import_keys_with_password(file_path: String, password: String) -> Result<ImportResult, String> {
+    println!("🔍 import_keys_with_password: Importing with password protection");
+    
+    // Read encrypted file
+    let encrypted_content = fs::read(&file_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    
+    // Decrypt with the key derivation described in the file header
+    let file = vault::parse(&encrypted_content)?;
+    let decrypted_content = decrypt_password_file(&file, &password)?;
     
     // Parse keys from JSON
-    let imported_keys: Vec<SshKey> = serde_json::from_str(&decrypted_content)
+    let imported_keys: Vec<SshKey> = parse_keys(&decrypted_content)
         .map_err(|e| format!("Failed to parse keys file: {}", e))?;
     
     println!("🔍 import_keys_with_password: Successfully decrypted {} keys", imported_keys.len());
@@ -872,28 +1482,22 @@ pub fn import_keys_with_password(file_path: String, password: String) -> Result<
     let current_keys = get_cached_keys()?;
     println!("🔍 import_keys_with_password: Current keys count: {}", current_keys.len());
     
-    // Create a set of existing key IDs to check for duplicates
-    let existing_ids: std::collections::HashSet<String> = current_keys.iter()
-        .map(|key| key.id.clone())
-        .collect();
-    
-    // Filter out duplicates from imported keys
-    let new_keys: Vec<SshKey> = imported_keys.iter()
-        .filter(|key| !existing_ids.contains(&key.id))
-        .cloned()
-        .collect();
+    // Filter out keys already in the store, matched by id or by key
+    let imported_total = imported_keys.len();
+    let (new_keys, duplicates) = partition_duplicates(&current_keys, imported_keys);
     
-    let duplicate_count = imported_keys.len() - new_keys.len();
+    let duplicate_count = duplicates.len();
     println!("🔍 import_keys_with_password: New keys: {}, Duplicates: {}", new_keys.len(), duplicate_count);
     
     if new_keys.len() == 0 {
         // All keys were duplicates - return current keys with duplicate info
-        println!("✅ import_keys_with_password: All {} keys were duplicates", imported_keys.len());
+        println!("✅ import_keys_with_password: All {} keys were duplicates", imported_total);
         return Ok(ImportResult {
-            keys: current_keys.clone(),
+            keys: without_private_keys(current_keys.clone()),
             imported_count: 0,
-            duplicate_count: imported_keys.len(),
+            duplicate_count,
             total_in_store: current_keys.len(),
+            duplicates,
         });
     }
     
@@ -908,22 +1512,810 @@ pub fn import_keys_with_password(file_path: String, password: String) -> Result<
     println!("✅ import_keys_with_password: Successfully imported {} new keys ({} duplicates skipped)", new_keys.len(), duplicate_count);
     
     Ok(ImportResult {
-        keys: merged_keys.clone(),
+        keys: without_private_keys(merged_keys.clone()),
         imported_count: new_keys.len(),
         duplicate_count,
         total_in_store: merged_keys.len(),
+        duplicates,
     })
 }
 
 #[tauri::command]
 pub fn get_encryption_mode() -> Result<String, String> {
-    if let Ok(password_key) = PASSWORD_KEY.lock() {
-        if password_key.is_some() {
-            Ok("password".to_string())
-        } else {
-            Ok("machine".to_string())
-        }
+    if get_password_key().is_some() || read_vault_key_source()? == Some(KeySource::Password) {
+        Ok("password".to_string())
     } else {
         Ok("machine".to_string())
     }
-} 
\ No newline at end of file
+}
+
+#[derive(Serialize)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub path: String,
+    pub created: DateTime<Utc>,
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+pub struct BackupPreview {
+    pub file_name: String,
+    pub created: DateTime<Utc>,
+    pub key_count: usize,
+    pub encryption_mode: String,
+}
+
+#[tauri::command]
+pub fn list_backups() -> Result<Vec<BackupInfo>, String> {
+    let backups = backup::list_backups(&get_backups_dir()?, &get_keys_file_path()?)?;
+    
+    Ok(backups.into_iter()
+        .map(|b| BackupInfo {
+            file_name: b.file_name,
+            path: b.path.to_string_lossy().to_string(),
+            created: b.created,
+            size: b.size,
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub fn preview_backup(file_name: String, password: Option<String>) -> Result<BackupPreview, String> {
+    let backup = backup::find_backup(&get_backups_dir()?, &get_keys_file_path()?, &file_name)?;
+    let encrypted_content = fs::read(&backup.path)
+        .map_err(|e| format!("Failed to read backup: {}", e))?;
+    
+    let encryption_mode = match vault::parse(&encrypted_content)?.key_source() {
+        Some(KeySource::Password) => "password",
+        _ => "machine",
+    };
+    let keys = decrypt_backup(&encrypted_content, password.as_deref())?;
+    
+    Ok(BackupPreview {
+        file_name: backup.file_name,
+        created: backup.created,
+        key_count: keys.len(),
+        encryption_mode: encryption_mode.to_string(),
+    })
+}
+
+// Replace the current keys with those from a backup. The current keys file is
+// itself backed up first, so a restore can be undone.
+#[tauri::command]
+pub fn restore_backup(file_name: String, password: Option<String>) -> Result<Vec<SshKey>, String> {
+    println!("Restoring backup: {}", file_name);
+    let backup = backup::find_backup(&get_backups_dir()?, &get_keys_file_path()?, &file_name)?;
+    let encrypted_content = fs::read(&backup.path)
+        .map_err(|e| format!("Failed to read backup: {}", e))?;
+    
+    let keys = decrypt_backup(&encrypted_content, password.as_deref())?;
+    
+    // Re-encrypt with the vault's current key rather than the backup's
+    update_cache_and_save(keys.clone())?;
+    
+    println!("Restored {} keys from backup {}", keys.len(), file_name);
+    Ok(without_private_keys(keys))
+}
+
+#[tauri::command]
+pub fn get_backup_limit() -> Result<usize, String> {
+    Ok(load_settings()?.backup_limit)
+}
+
+// Set how many backups to keep for each keys file; 0 disables backups
+#[tauri::command]
+pub fn set_backup_limit(limit: usize) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.backup_limit = limit;
+    save_settings(&settings)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordKdfSettings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+#[tauri::command]
+pub fn get_password_kdf_settings() -> Result<PasswordKdfSettings, String> {
+    match load_settings()?.password_kdf {
+        Kdf::Argon2id { memory_kib, iterations, parallelism } => Ok(PasswordKdfSettings {
+            memory_kib,
+            iterations,
+            parallelism,
+        }),
+        _ => Err("Password key derivation is not using Argon2id".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn set_password_kdf_settings(settings: PasswordKdfSettings) -> Result<(), String> {
+    vault::argon2_params(settings.memory_kib, settings.iterations, settings.parallelism)?;
+    
+    let kdf = Kdf::Argon2id {
+        memory_kib: settings.memory_kib,
+        iterations: settings.iterations,
+        parallelism: settings.parallelism,
+    };
+    let mut current = load_settings()?;
+    current.password_kdf = kdf;
+    save_settings(&current)
+}
+
+// Get the randomart of a stored key, for comparing keys by eye
+#[tauri::command]
+pub fn get_key_randomart(id: String) -> Result<String, String> {
+    let keys = get_cached_keys()?;
+    let key = keys.iter().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    
+    Ok(public_key::parse(&key.key)?.randomart())
+}
+
+// Assess every stored key against the key policy
+#[tauri::command]
+pub fn get_key_strength_report() -> Result<Vec<KeyStrength>, String> {
+    let keys = get_cached_keys()?;
+    let policy = load_settings()?.key_policy;
+    
+    Ok(keys.iter().map(|key| strength::assess(key, &policy)).collect())
+}
+
+#[tauri::command]
+pub fn get_key_policy() -> Result<KeyPolicy, String> {
+    Ok(load_settings()?.key_policy)
+}
+
+#[tauri::command]
+pub fn set_key_policy(mut policy: KeyPolicy) -> Result<(), String> {
+    if policy.min_rsa_bits < 1024 || policy.min_rsa_bits > 16384 {
+        return Err("Minimum RSA key size must be between 1024 and 16384 bits".to_string());
+    }
+    
+    policy.require_security_key_tags = policy.require_security_key_tags.iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    
+    let mut settings = load_settings()?;
+    settings.key_policy = policy;
+    save_settings(&settings)
+}
+
+#[derive(Serialize)]
+pub struct CertificateStatus {
+    pub id: String,
+    pub name: String,
+    pub certificate: Certificate,
+    pub validity: CertificateValidity,
+}
+
+// List the stored certificates with whether they are valid, expired, not yet
+// valid, or expire within the given number of days (30 by default)
+#[tauri::command]
+pub fn list_certificates(expiring_within_days: Option<u32>) -> Result<Vec<CertificateStatus>, String> {
+    let keys = get_cached_keys()?;
+    let now = Utc::now();
+    let soon = chrono::Duration::days(i64::from(expiring_within_days.unwrap_or(DEFAULT_CERTIFICATE_EXPIRY_WARNING_DAYS)));
+    
+    Ok(keys.into_iter()
+        .filter_map(|key| {
+            let certificate = key.certificate?;
+            Some(CertificateStatus {
+                validity: certificate.validity(now, soon),
+                id: key.id,
+                name: key.name,
+                certificate,
+            })
+        })
+        .collect())
+}
+
+// Designate a stored key as a certificate authority. Its private key (OpenSSH
+// format, optionally passphrase-protected) is stored in the vault, unless it
+// is already there, e.g. because the key was generated here.
+#[tauri::command]
+pub fn set_certificate_authority(id: String, private_key: Option<String>) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    let key = keys.iter_mut().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    
+    if key.certificate.is_some() {
+        return Err("A certificate can't be used as a certificate authority".to_string());
+    }
+    let private_key = private_key.or_else(|| key.private_key.clone())
+        .ok_or("The private key of a certificate authority is required")?;
+    let parsed = private_key::parse(&private_key)?;
+    if private_key::fingerprint(&parsed) != key.fingerprint {
+        return Err("The private key does not match this public key".to_string());
+    }
+    
+    set_private_key(key, Some(private_key.trim().to_string()))?;
+    key.is_ca = true;
+    key.last_modified = Utc::now();
+    
+    let updated_key = key.clone();
+    update_cache_and_save(keys)?;
+    
+    println!("Key {} is now a certificate authority", updated_key.fingerprint);
+    Ok(without_private_key(updated_key))
+}
+
+// Stop using a key as a certificate authority. Its private key stays stored.
+#[tauri::command]
+pub fn remove_certificate_authority(id: String) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    let key = keys.iter_mut().find(|k| k.id == id && k.is_ca)
+        .ok_or("Certificate authority not found")?;
+    
+    key.is_ca = false;
+    key.last_modified = Utc::now();
+    
+    let updated_key = key.clone();
+    update_cache_and_save(keys)?;
+    
+    Ok(without_private_key(updated_key))
+}
+
+// Sign a stored public key with a certificate authority. The certificate is
+// added to the vault and, if `output_path` is given, written there as an
+// OpenSSH `-cert.pub` file.
+#[tauri::command]
+pub fn sign_certificate(ca_id: String, key_id: String, request: CertificateRequest, passphrase: Option<String>, output_path: Option<String>) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    
+    let authority = keys.iter().find(|k| k.id == ca_id && k.is_ca)
+        .ok_or("Certificate authority not found")?;
+    let ca_key = private_key::parse(authority.private_key.as_deref().unwrap_or_default())?;
+    
+    let subject = keys.iter().find(|k| k.id == key_id)
+        .ok_or("Key not found")?;
+    if subject.certificate.is_some() {
+        return Err("Certificates can't be signed again; sign the key they certify instead".to_string());
+    }
+    
+    // Continue the CA's serial numbers unless one is given
+    let serial = match request.serial {
+        Some(serial) => serial,
+        None => keys.iter()
+            .filter_map(|k| k.certificate.as_ref())
+            .filter(|c| c.ca_fingerprint == authority.fingerprint)
+            .map(|c| c.serial)
+            .max()
+            .map_or(Some(1), |serial| serial.checked_add(1))
+            .ok_or("This CA has used the highest certificate serial; give a serial explicitly")?,
+    };
+    
+    let certificate = ca::sign(&ca_key, passphrase.as_deref(), &subject.key, &request, serial)?;
+    let parsed = public_key::parse(&certificate)?;
+    
+    let name = merge::unique_name(&keys, &format!("{} certificate", subject.name));
+    let new_key = new_key_entry(name, subject.tag.clone(), &certificate, parsed);
+    
+    if let Some(output_path) = &output_path {
+        fs_util::write_new_file(Path::new(output_path), format!("{}\n", new_key.key).as_bytes(), 0o644)
+            .map_err(|e| format!("Failed to write certificate: {}", e))?;
+    }
+    
+    keys.push(new_key.clone());
+    if let Err(e) = update_cache_and_save(keys) {
+        if let Some(output_path) = &output_path {
+            let _ = fs::remove_file(output_path);
+        }
+        return Err(e);
+    }
+    
+    println!("Signed certificate serial {} for key ID {:?}", serial, request.key_id);
+    Ok(new_key)
+}
+
+// Mark a key or certificate as revoked, to be included in generated KRLs
+#[tauri::command]
+pub fn revoke_key(id: String, reason: Option<String>) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    let key = keys.iter_mut().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    
+    let now = Utc::now();
+    key.revoked = Some(Revocation { reason, revoked_at: now });
+    key.last_modified = now;
+    
+    let updated_key = key.clone();
+    update_cache_and_save(keys)?;
+    
+    println!("Revoked key {}", updated_key.fingerprint);
+    Ok(without_private_key(updated_key))
+}
+
+#[tauri::command]
+pub fn unrevoke_key(id: String) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    let key = keys.iter_mut().find(|k| k.id == id && k.revoked.is_some())
+        .ok_or("Revoked key not found")?;
+    
+    key.revoked = None;
+    key.last_modified = Utc::now();
+    
+    let updated_key = key.clone();
+    update_cache_and_save(keys)?;
+    
+    Ok(without_private_key(updated_key))
+}
+
+// Revoke a certificate by serial number, for certificates the CA issued that
+// aren't stored here
+#[tauri::command]
+pub fn revoke_certificate_serial(ca_id: String, serial: u64, reason: Option<String>) -> Result<SshKey, String> {
+    // OpenSSH doesn't allow revoking serial 0, which is what unnumbered certificates get
+    if serial == 0 {
+        return Err("Serial 0 can't be revoked; revoke the certificate itself instead".to_string());
+    }
+    
+    let mut keys = get_cached_keys()?;
+    let ca = keys.iter_mut().find(|k| k.id == ca_id)
+        .ok_or("Key not found")?;
+    if ca.certificate.is_some() {
+        return Err("A certificate can't be a certificate authority".to_string());
+    }
+    
+    let now = Utc::now();
+    ca.revoked_serials.retain(|revoked| revoked.serial != serial);
+    ca.revoked_serials.push(RevokedSerial { serial, reason, revoked_at: now });
+    ca.revoked_serials.sort_by_key(|revoked| revoked.serial);
+    ca.last_modified = now;
+    
+    let updated_key = ca.clone();
+    update_cache_and_save(keys)?;
+    
+    Ok(without_private_key(updated_key))
+}
+
+#[derive(Serialize)]
+pub struct KrlSummary {
+    pub path: String,
+    pub revoked_keys: usize,
+    pub revoked_serials: usize,
+    pub revoked_key_ids: usize,
+}
+
+// Build a KRL from the revoked keys and serials, the same way
+// `ssh-keygen -k` would revoke them
+fn build_krl(keys: &[SshKey], comment: String) -> Krl {
+    let now = Utc::now();
+    let mut revocations = Krl {
+        version: now.timestamp().max(0) as u64,
+        generated: Some(now),
+        comment,
+        ..Krl::default()
+    };
+    
+    for key in keys.iter().filter(|k| k.revoked.is_some()) {
+        let parsed = match public_key::parse(&key.key) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!("Leaving revoked key '{}' out of the KRL: {}", key.name, e);
+                continue;
+            }
+        };
+        
+        // Certificates are revoked by serial, or by key ID if they have no serial.
+        // Without the CA key only the key ID can be revoked, for any CA, as
+        // OpenSSH doesn't allow serials without a CA.
+        match &parsed.certificate {
+            None => {
+                revocations.keys.insert(parsed.blob);
+            }
+            Some(certificate) => {
+                let Some(ca_key) = certificate.ca_key_blob() else {
+                    println!("Revoking certificate '{}' by key ID \"{}\" for any CA, as its CA key is missing", key.name, certificate.key_id);
+                    revocations.certificates.entry(Vec::new()).or_default()
+                        .key_ids.insert(certificate.key_id.clone());
+                    continue;
+                };
+                let revoked = revocations.certificates.entry(ca_key).or_default();
+                if certificate.serial == 0 {
+                    revoked.key_ids.insert(certificate.key_id.clone());
+                } else {
+                    krl::add_serials(revoked, [(certificate.serial, certificate.serial)]);
+                }
+            }
+        }
+    }
+    
+    for ca in keys.iter().filter(|k| !k.revoked_serials.is_empty()) {
+        let Ok(parsed) = public_key::parse(&ca.key) else { continue };
+        let revoked = revocations.certificates.entry(parsed.blob).or_default();
+        krl::add_serials(revoked, ca.revoked_serials.iter().map(|r| (r.serial, r.serial)));
+    }
+    
+    revocations
+}
+
+// Write an OpenSSH KRL file for `RevokedKeys` in sshd_config
+#[tauri::command]
+pub fn generate_krl(file_path: String, comment: Option<String>) -> Result<KrlSummary, String> {
+    let keys = get_cached_keys()?;
+    let revocations = build_krl(&keys, comment.unwrap_or_else(|| "Generated by SSH Kim".to_string()));
+    
+    fs_util::write_atomic(Path::new(&file_path), &revocations.to_bytes())
+        .map_err(|e| format!("Failed to write KRL file: {}", e))?;
+    
+    let certificates = revocations.certificates.values();
+    let summary = KrlSummary {
+        path: file_path,
+        revoked_keys: revocations.keys.len(),
+        revoked_serials: certificates.clone()
+            .flat_map(|revoked| revoked.serials.iter())
+            .map(|(low, high)| high - low + 1)
+            .sum::<u64>() as usize,
+        revoked_key_ids: certificates.map(|revoked| revoked.key_ids.len()).sum(),
+    };
+    
+    println!("Wrote KRL {} with {} keys and {} certificate serials", summary.path, summary.revoked_keys, summary.revoked_serials);
+    Ok(summary)
+}
+
+fn read_krl_file(file_path: &str) -> Result<Krl, String> {
+    let bytes = fs::read(file_path)
+        .map_err(|e| format!("Failed to read KRL file: {}", e))?;
+    krl::parse(&bytes)
+}
+
+#[derive(Serialize)]
+pub struct KrlCheck {
+    pub revoked: bool,
+    pub reason: Option<String>,
+}
+
+// Check whether a public key or certificate is revoked by a KRL file
+#[tauri::command]
+pub fn check_krl(file_path: String, key_content: String) -> Result<KrlCheck, String> {
+    let revocations = read_krl_file(&file_path)?;
+    // The key may be pasted in RFC4716 or PEM form
+    let key_content = key_format::to_openssh(&key_content)?;
+    let reason = revocations.check(&public_key::parse(&key_content)?);
+    
+    Ok(KrlCheck {
+        revoked: reason.is_some(),
+        reason,
+    })
+}
+
+#[derive(Serialize)]
+pub struct KrlMatch {
+    pub id: String,
+    pub name: String,
+    pub reason: String,
+}
+
+// List the stored keys and certificates that a KRL file revokes
+#[tauri::command]
+pub fn check_keys_against_krl(file_path: String) -> Result<Vec<KrlMatch>, String> {
+    let revocations = read_krl_file(&file_path)?;
+    let keys = get_cached_keys()?;
+    
+    Ok(keys.into_iter()
+        .filter_map(|key| {
+            let reason = revocations.check(&public_key::parse(&key.key).ok()?)?;
+            Some(KrlMatch { id: key.id, name: key.name, reason })
+        })
+        .collect())
+}
+
+#[derive(Serialize)]
+pub struct GeneratedKey {
+    pub key: SshKey,
+    // Set when the key pair was written to the SSH directory
+    pub private_key_path: Option<String>,
+    pub public_key_path: Option<String>,
+}
+
+// Generate a key pair ("ed25519", "rsa" or "ecdsa", with `bits` choosing the
+// RSA size or ECDSA curve) and add it to the store. The private key is kept
+// in the vault, protected with the passphrase if one is given. With a
+// `file_name`, both halves are also written to the SSH directory.
+// Runs off the main thread, since large RSA keys take tens of seconds.
+#[tauri::command(async)]
+pub fn generate_ssh_key(
+    name: String,
+    tag: Option<String>,
+    key_type: String,
+    bits: Option<u32>,
+    comment: Option<String>,
+    passphrase: Option<String>,
+    file_name: Option<String>,
+) -> Result<GeneratedKey, String> {
+    let name_taken = |keys: &[SshKey]| keys.iter().any(|k| k.name.trim().to_lowercase() == name.trim().to_lowercase());
+    if name_taken(&get_cached_keys()?) {
+        return Err("A key with this name already exists".to_string());
+    }
+    
+    // Check the destination before spending time on key generation
+    let paths = match file_name.as_deref().map(str::trim) {
+        Some(file_name) => {
+            if file_name.is_empty() || file_name.contains(['/', '\\']) || file_name.starts_with('.') {
+                return Err("The file name must be a plain name such as id_ed25519".to_string());
+            }
+            let private_path = get_default_ssh_dir()?.join(file_name);
+            let public_path = private_path.with_file_name(format!("{}.pub", file_name));
+            if private_path.exists() || public_path.exists() {
+                return Err(format!("{} already exists", private_path.display()));
+            }
+            Some((private_path, public_path))
+        }
+        None => None,
+    };
+    
+    let comment = comment.unwrap_or_else(|| name.clone());
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    let generated = private_key::generate(&key_type, bits, &comment)?;
+    let private_key_text = private_key::encode(&generated, passphrase.as_deref(), private_key::DEFAULT_KDF_ROUNDS)?;
+    let public_key_text = private_key::public_key_line(&generated)?;
+    
+    // Other commands may have changed the keys while the key was generated
+    let mut keys = get_cached_keys()?;
+    if name_taken(&keys) {
+        return Err("A key with this name already exists".to_string());
+    }
+    let mut new_key = new_key_entry(name, tag, &public_key_text, public_key::parse(&public_key_text)?);
+    set_private_key(&mut new_key, Some(private_key_text.clone()))?;
+    
+    if let Some((private_path, public_path)) = &paths {
+        fs_util::create_private_dir(&get_default_ssh_dir()?)
+            .map_err(|e| format!("Failed to create SSH directory: {}", e))?;
+        fs_util::write_new_file(private_path, private_key_text.as_bytes(), 0o600)
+            .map_err(|e| format!("Failed to write private key: {}", e))?;
+        if let Err(e) = fs_util::write_new_file(public_path, format!("{}\n", public_key_text).as_bytes(), 0o644) {
+            let _ = fs::remove_file(private_path);
+            return Err(format!("Failed to write public key: {}", e));
+        }
+    }
+    
+    keys.push(new_key.clone());
+    if let Err(e) = update_cache_and_save(keys) {
+        // Don't leave key files behind that the vault doesn't know about
+        if let Some((private_path, public_path)) = &paths {
+            let _ = fs::remove_file(private_path);
+            let _ = fs::remove_file(public_path);
+        }
+        return Err(e);
+    }
+    
+    println!("Generated {} key {}", new_key.algorithm, new_key.fingerprint);
+    Ok(GeneratedKey {
+        key: without_private_key(new_key),
+        private_key_path: paths.as_ref().map(|(path, _)| path.to_string_lossy().to_string()),
+        public_key_path: paths.as_ref().map(|(_, path)| path.to_string_lossy().to_string()),
+    })
+}
+
+// Re-authenticate before handing out a private key: the master password must
+// be entered again. Without a master password there is nothing to check it
+// against, so private keys stay in the vault.
+fn verify_master_password(password: &str) -> Result<(), String> {
+    let password_key = get_password_key()
+        .ok_or("Set a master password before revealing or exporting private keys")?;
+    
+    let mut derived = vault::derive_key(&password_key.kdf, password, Some(&password_key.salt))?;
+    let matches = derived.iter().zip(password_key.key.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0;
+    derived.zeroize();
+    
+    if matches {
+        Ok(())
+    } else {
+        Err("Incorrect master password".to_string())
+    }
+}
+
+#[derive(Serialize)]
+pub struct PrivateKeyImport {
+    pub key: SshKey,
+    // False if the private key was added to an existing entry
+    pub created: bool,
+    // The private key has its own passphrase
+    pub encrypted: bool,
+}
+
+// Import a private key file such as ~/.ssh/id_ed25519. It is stored with the
+// entry holding its public key, or in a new entry (using the .pub file next to
+// it, if there is one, for the comment). PuTTY keys are converted to OpenSSH
+// format and need their passphrase, if they have one, which they keep.
+#[tauri::command]
+pub fn import_private_key(file_path: String, name: Option<String>, tag: Option<String>, passphrase: Option<String>) -> Result<PrivateKeyImport, String> {
+    let path = PathBuf::from(&file_path);
+    let mut text = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read private key file: {}", e))?;
+    // A PuTTY key's comment is readable even when the key is encrypted
+    let mut putty_public_key = None;
+    if ppk::is_ppk(&text) {
+        putty_public_key = Some(ppk::parse(&text)?.public_key_line());
+        text = ppk::to_openssh(&text, passphrase.as_deref().filter(|p| !p.is_empty()))?;
+    } else if matches!(
+        private_key::detect_format(&text),
+        Some(PrivateKeyFormat::Pkcs1 | PrivateKeyFormat::Pkcs8 | PrivateKeyFormat::Sec1)
+    ) {
+        // Stored keys are always in OpenSSH format
+        text = private_key::pem_to_openssh(&text)?;
+    }
+    let parsed = private_key::parse(&text)?;
+    let fingerprint = private_key::fingerprint(&parsed);
+    
+    let mut keys = get_cached_keys()?;
+    
+    let existing = keys.iter_mut()
+        .find(|k| k.fingerprint == fingerprint && k.certificate.is_none());
+    let (key, created) = match existing {
+        Some(key) if key.has_private_key => {
+            return Err(format!("The private key for '{}' is already stored", key.name));
+        }
+        Some(key) => {
+            set_private_key(key, Some(text.trim().to_string()))?;
+            key.last_modified = Utc::now();
+            (key.clone(), false)
+        }
+        None => {
+            let mut public_path = path.clone().into_os_string();
+            public_path.push(".pub");
+            let public_key_text = fs::read_to_string(&public_path).ok()
+                .filter(|content| public_key::parse(content).is_ok_and(|p| p.fingerprint_sha256() == fingerprint))
+                .or(putty_public_key)
+                .map(Ok)
+                .unwrap_or_else(|| private_key::public_key_line(&parsed))?;
+            
+            let file_name = path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Imported key".to_string());
+            let name = merge::unique_name(&keys, &name.unwrap_or(file_name));
+            
+            let mut key = new_key_entry(name, tag, &public_key_text, public_key::parse(&public_key_text)?);
+            set_private_key(&mut key, Some(text.trim().to_string()))?;
+            keys.push(key.clone());
+            (key, true)
+        }
+    };
+    
+    update_cache_and_save(keys)?;
+    
+    println!("Imported private key {} from {}", key.fingerprint, file_path);
+    Ok(PrivateKeyImport {
+        encrypted: key.private_key_encrypted,
+        key: without_private_key(key),
+        created,
+    })
+}
+
+// Return a stored private key in OpenSSH format, after re-entering the master password
+#[tauri::command]
+pub fn reveal_private_key(id: String, password: String) -> Result<String, String> {
+    verify_master_password(&password)?;
+    
+    let keys = get_cached_keys()?;
+    let key = keys.into_iter().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    
+    println!("Revealed private key {}", key.fingerprint);
+    key.private_key.ok_or_else(|| "No private key is stored for this key".to_string())
+}
+
+// Write a stored private key to `file_path` (mode 0600) and its public key
+// next to it as `.pub`, after re-entering the master password. Existing
+// files are not overwritten.
+#[tauri::command]
+pub fn export_private_key(id: String, file_path: String, password: String) -> Result<(), String> {
+    verify_master_password(&password)?;
+    
+    let keys = get_cached_keys()?;
+    let key = keys.iter().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    let private_key = key.private_key.as_deref()
+        .ok_or("No private key is stored for this key")?;
+    
+    let path = PathBuf::from(&file_path);
+    let mut public_path = path.clone().into_os_string();
+    public_path.push(".pub");
+    let public_path = PathBuf::from(public_path);
+    
+    fs_util::write_new_file(&path, format!("{}\n", private_key.trim_end()).as_bytes(), 0o600)
+        .map_err(|e| format!("Failed to write private key: {}", e))?;
+    if !public_path.exists() {
+        fs_util::write_new_file(&public_path, format!("{}\n", key.key).as_bytes(), 0o644)
+            .map_err(|e| format!("Failed to write public key: {}", e))?;
+    }
+    
+    println!("Exported private key {} to {}", key.fingerprint, file_path);
+    Ok(())
+}
+
+// Delete a stored private key; the public key stays. A CA without its private
+// key can't sign, so it stops being a CA.
+#[tauri::command]
+pub fn remove_private_key(id: String) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    let key = keys.iter_mut().find(|k| k.id == id && k.has_private_key)
+        .ok_or("No private key is stored for this key")?;
+    
+    set_private_key(key, None)?;
+    key.is_ca = false;
+    key.last_modified = Utc::now();
+    
+    let updated_key = key.clone();
+    update_cache_and_save(keys)?;
+    
+    Ok(updated_key)
+}
+
+// Re-encrypt a stored private key under a new passphrase, or remove its
+// passphrase if none is given, like `ssh-keygen -p`. `rounds` sets the
+// bcrypt-pbkdf rounds for the new passphrase (16 by default).
+#[tauri::command]
+pub fn change_private_key_passphrase(id: String, old_passphrase: Option<String>, new_passphrase: Option<String>, rounds: Option<u32>) -> Result<SshKey, String> {
+    let mut keys = get_cached_keys()?;
+    let key = keys.iter_mut().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    let current = private_key::parse(key.private_key.as_deref().ok_or("No private key is stored for this key")?)?;
+    
+    let unlocked = private_key::unlock(&current, old_passphrase.as_deref())?;
+    record_security_key_flags(key, &unlocked);
+    let new_passphrase = new_passphrase.filter(|p| !p.is_empty());
+    let reencrypted = private_key::encode(&unlocked, new_passphrase.as_deref(), rounds.unwrap_or(private_key::DEFAULT_KDF_ROUNDS))?;
+    
+    // The key pair itself must not change
+    if private_key::fingerprint(&private_key::parse(&reencrypted)?) != key.fingerprint {
+        return Err("Re-encrypting the private key changed its public key; nothing was saved".to_string());
+    }
+    
+    set_private_key(key, Some(reencrypted))?;
+    key.last_modified = Utc::now();
+    
+    let updated_key = key.clone();
+    update_cache_and_save(keys)?;
+    
+    println!("Changed the passphrase of private key {}", updated_key.fingerprint);
+    Ok(without_private_key(updated_key))
+}
+
+// A stored public key as an OpenSSH line, an RFC 4716 ("SSH2") block or PEM,
+// like `ssh-keygen -e -m RFC4716|PKCS8`. Also written to `file_path` if one
+// is given; existing files are not overwritten.
+#[tauri::command]
+pub fn export_public_key(id: String, format: PublicKeyFormat, file_path: Option<String>) -> Result<String, String> {
+    let keys = get_cached_keys()?;
+    let key = keys.iter().find(|k| k.id == id)
+        .ok_or("Key not found")?;
+    
+    let converted = key_format::convert(&key.key, format)?;
+    
+    if let Some(file_path) = file_path {
+        fs_util::write_new_file(&PathBuf::from(&file_path), converted.as_bytes(), 0o644)
+            .map_err(|e| format!("Failed to write {}: {}", file_path, e))?;
+        println!("Exported public key {} to {}", key.fingerprint, file_path);
+    }
+    
+    Ok(converted)
+}
+
+// Convert a private key file between PuTTY (.ppk) and OpenSSH format, in
+// whichever direction applies, like `puttygen -O private-openssh-new` and
+// back. The passphrase is needed for protected keys and is kept. Existing
+// files are not overwritten.
+#[tauri::command]
+pub fn convert_private_key_file(input_path: String, output_path: String, passphrase: Option<String>) -> Result<(), String> {
+    let text = fs::read_to_string(&input_path)
+        .map_err(|e| format!("Failed to read private key file: {}", e))?;
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    
+    let converted = if ppk::is_ppk(&text) {
+        ppk::to_openssh(&text, passphrase.as_deref())?
+    } else {
+        let parsed = private_key::parse(&text)?;
+        let passphrase = passphrase.filter(|_| parsed.is_encrypted());
+        ppk::encode(&private_key::unlock(&parsed, passphrase.as_deref())?, passphrase.as_deref())?
+    };
+    
+    fs_util::write_new_file(&PathBuf::from(&output_path), converted.as_bytes(), 0o600)
+        .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+    
+    println!("Converted private key {} to {}", input_path, output_path);
+    Ok(())
+}
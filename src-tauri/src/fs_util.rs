@@ -0,0 +1,115 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use fs4::fs_std::FileExt;
+use uuid::Uuid;
+
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+// Write a file so that readers only ever see the old or the new content:
+// write to a temporary file in the same directory, fsync it, then rename it
+// over the target. The file is created readable by the owner only.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name.to_string_lossy(), Uuid::new_v4().simple()));
+
+    let result = write_new_file(&tmp_path, contents, 0o600)
+        .and_then(|_| fs::rename(&tmp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Persist the rename itself
+    sync_dir(dir);
+    Ok(())
+}
+
+// Advisory lock held on a sidecar lock file; released when dropped
+pub struct FileLock {
+    _file: fs::File,
+}
+
+// Take an exclusive advisory lock on `lock_path`, waiting up to `timeout` for
+// another process to release it. Only processes that also lock the same path
+// are excluded; the data file itself can still be read freely.
+pub fn lock_exclusive(lock_path: &Path, timeout: Duration) -> io::Result<FileLock> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(false);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let file = options.open(lock_path)?;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if file.try_lock_exclusive()? {
+            return Ok(FileLock { _file: file });
+        }
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "locked by another process"));
+        }
+        thread::sleep(LOCK_RETRY_INTERVAL);
+    }
+}
+
+// Create a directory (and its parents) if missing, readable by the owner
+// only. An existing directory that others can read, such as one made by an
+// older version, is tightened to the same permissions.
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    if !dir.exists() {
+        fs::create_dir_all(dir)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if fs::metadata(dir)?.permissions().mode() & 0o077 != 0 {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Create a file that must not exist yet, with the given Unix permissions
+// (e.g. 0o600 for private keys, 0o644 for public keys)
+pub fn write_new_file(path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+// Windows has no directory handles to fsync; the rename is already durable
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
@@ -0,0 +1,179 @@
+use base64::{Engine as _, engine::general_purpose};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::{DecodePublicKey, EncodePublicKey, LineEnding};
+use serde::Deserialize;
+use ssh_key::public::{EcdsaPublicKey, KeyData};
+use crate::public_key::{self, WireReader};
+
+// Public key formats besides OpenSSH's one-line form, converted the way
+// `ssh-keygen -i` and `ssh-keygen -e` do: RFC 4716 ("SSH2") blocks, used by
+// network gear and Windows tools, and PEM as written by OpenSSL
+const SSH2_BEGIN: &str = "---- BEGIN SSH2 PUBLIC KEY ----";
+const SSH2_END: &str = "---- END SSH2 PUBLIC KEY ----";
+// RFC 4716 lines are at most 72 bytes, including a continuation backslash
+const SSH2_LINE_LENGTH: usize = 70;
+
+const PEM_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----";
+const PEM_RSA_PUBLIC_KEY: &str = "-----BEGIN RSA PUBLIC KEY-----";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PublicKeyFormat {
+    // "<algorithm> <base64 key> [comment]"
+    Openssh,
+    // "---- BEGIN SSH2 PUBLIC KEY ----"
+    Rfc4716,
+    // "-----BEGIN PUBLIC KEY-----" (X.509 SubjectPublicKeyInfo, which
+    // ssh-keygen calls PKCS8)
+    Pem,
+}
+
+// Convert a public key in any supported format to an OpenSSH line. OpenSSH
+// lines are returned as they are, trimmed.
+pub fn to_openssh(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.starts_with(SSH2_BEGIN) {
+        from_rfc4716(text)
+    } else if text.starts_with(PEM_PUBLIC_KEY) || text.starts_with(PEM_RSA_PUBLIC_KEY) {
+        from_pem(text)
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+// Convert an OpenSSH public key line to `format`
+pub fn convert(line: &str, format: PublicKeyFormat) -> Result<String, String> {
+    match format {
+        PublicKeyFormat::Openssh => Ok(format!("{}\n", line.trim())),
+        PublicKeyFormat::Rfc4716 => to_rfc4716(line),
+        PublicKeyFormat::Pem => to_pem(line),
+    }
+}
+
+// The Comment header becomes the key's comment. Other headers (such as
+// Subject) have no place in an OpenSSH line and are dropped.
+fn from_rfc4716(text: &str) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let mut continued = false;
+    let mut ended = false;
+    for line in text.lines().skip(1).map(str::trim) {
+        if line == SSH2_END {
+            ended = true;
+            break;
+        }
+        // A header ending in a backslash continues on the next line
+        let (content, continues) = match line.strip_suffix('\\') {
+            Some(content) => (content, true),
+            None => (line, false),
+        };
+        match lines.last_mut() {
+            Some(last) if continued => *last += content,
+            _ => lines.push(content.to_string()),
+        }
+        continued = continues;
+    }
+    if !ended {
+        return Err("Invalid SSH2 public key: missing the END line".to_string());
+    }
+
+    let mut comment = None;
+    let mut encoded = String::new();
+    for line in &lines {
+        // Headers are "Tag: value"; base64 never contains a colon
+        match line.split_once(':') {
+            Some((tag, value)) if tag.trim().eq_ignore_ascii_case("Comment") => {
+                let value = value.trim();
+                let value = value.strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                comment = Some(value.to_string()).filter(|c| !c.is_empty());
+            }
+            Some(_) => {}
+            None => encoded += line,
+        }
+    }
+
+    let blob = general_purpose::STANDARD.decode(&encoded)
+        .map_err(|_| "Invalid SSH2 public key: the key data is not valid base64".to_string())?;
+    let algorithm = WireReader::new(&blob).read_string()?;
+
+    let mut line = format!("{} {}", algorithm, encoded);
+    if let Some(comment) = comment {
+        line = format!("{} {}", line, comment);
+    }
+    public_key::parse(&line)?;
+    Ok(line)
+}
+
+// RSA (PKCS#1 or SubjectPublicKeyInfo), Ed25519 and NIST P-256, P-384 and
+// P-521 keys
+fn from_pem(text: &str) -> Result<String, String> {
+    let key_data = if text.starts_with(PEM_RSA_PUBLIC_KEY) {
+        let key = rsa::RsaPublicKey::from_pkcs1_pem(text)
+            .map_err(|e| format!("Invalid PEM public key: {}", e))?;
+        KeyData::Rsa(key.try_into().map_err(conversion_error)?)
+    } else if let Ok(key) = rsa::RsaPublicKey::from_public_key_pem(text) {
+        KeyData::Rsa(key.try_into().map_err(conversion_error)?)
+    } else if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(text) {
+        KeyData::Ed25519(key.into())
+    } else if let Ok(key) = p256::PublicKey::from_public_key_pem(text) {
+        KeyData::Ecdsa(EcdsaPublicKey::NistP256(key.to_encoded_point(false)))
+    } else if let Ok(key) = p384::PublicKey::from_public_key_pem(text) {
+        KeyData::Ecdsa(EcdsaPublicKey::NistP384(key.to_encoded_point(false)))
+    } else if let Ok(key) = p521::PublicKey::from_public_key_pem(text) {
+        KeyData::Ecdsa(EcdsaPublicKey::NistP521(key.to_encoded_point(false)))
+    } else {
+        return Err("Invalid PEM public key: expected an RSA, ECDSA (P-256, P-384 or P-521) or Ed25519 key".to_string());
+    };
+
+    ssh_key::PublicKey::new(key_data, "").to_openssh()
+        .map_err(conversion_error)
+}
+
+fn to_rfc4716(line: &str) -> Result<String, String> {
+    let parsed = public_key::parse(line)?;
+    let encoded = line.split_whitespace().nth(1).unwrap_or_default();
+
+    let mut out = format!("{}\n", SSH2_BEGIN);
+    if let Some(comment) = parsed.comment {
+        let header: Vec<char> = format!("Comment: \"{}\"", comment).chars().collect();
+        let chunks: Vec<String> = header.chunks(SSH2_LINE_LENGTH).map(|c| c.iter().collect()).collect();
+        out += &chunks.join("\\\n");
+        out.push('\n');
+    }
+    for chunk in encoded.as_bytes().chunks(SSH2_LINE_LENGTH) {
+        out += &String::from_utf8_lossy(chunk);
+        out.push('\n');
+    }
+    out += SSH2_END;
+    out.push('\n');
+    Ok(out)
+}
+
+fn to_pem(line: &str) -> Result<String, String> {
+    let key = ssh_key::PublicKey::from_openssh(line.trim())
+        .map_err(|_| "Only plain RSA, ECDSA and Ed25519 keys can be converted to PEM".to_string())?;
+
+    let pem = match key.key_data() {
+        KeyData::Rsa(key) => rsa::RsaPublicKey::try_from(key).map_err(conversion_error)?
+            .to_public_key_pem(LineEnding::LF),
+        KeyData::Ed25519(key) => ed25519_dalek::VerifyingKey::try_from(key).map_err(conversion_error)?
+            .to_public_key_pem(LineEnding::LF),
+        KeyData::Ecdsa(EcdsaPublicKey::NistP256(point)) => p256::PublicKey::from_sec1_bytes(point.as_bytes())
+            .map_err(|e| format!("Invalid ECDSA public key: {}", e))?
+            .to_public_key_pem(LineEnding::LF),
+        KeyData::Ecdsa(EcdsaPublicKey::NistP384(point)) => p384::PublicKey::from_sec1_bytes(point.as_bytes())
+            .map_err(|e| format!("Invalid ECDSA public key: {}", e))?
+            .to_public_key_pem(LineEnding::LF),
+        KeyData::Ecdsa(EcdsaPublicKey::NistP521(point)) => p521::PublicKey::from_sec1_bytes(point.as_bytes())
+            .map_err(|e| format!("Invalid ECDSA public key: {}", e))?
+            .to_public_key_pem(LineEnding::LF),
+        _ => return Err("Only plain RSA, ECDSA and Ed25519 keys can be converted to PEM".to_string()),
+    };
+    pem.map_err(|e| format!("Failed to encode PEM public key: {}", e))
+}
+
+fn conversion_error(e: ssh_key::Error) -> String {
+    format!("Failed to convert public key: {}", e)
+}
@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use crate::public_key::{PublicKey, WireReader};
+
+// OpenSSH key revocation lists, for `RevokedKeys` in sshd_config
+// (PROTOCOL.krl in the OpenSSH sources)
+const KRL_MAGIC: &[u8; 8] = b"SSHKRL\n\0";
+const KRL_FORMAT_VERSION: u32 = 1;
+
+const SECTION_CERTIFICATES: u8 = 1;
+const SECTION_EXPLICIT_KEY: u8 = 2;
+const SECTION_FINGERPRINT_SHA1: u8 = 3;
+const SECTION_SIGNATURE: u8 = 4;
+const SECTION_FINGERPRINT_SHA256: u8 = 5;
+
+const CERT_SERIAL_LIST: u8 = 0x20;
+const CERT_SERIAL_RANGE: u8 = 0x21;
+const CERT_SERIAL_BITMAP: u8 = 0x22;
+const CERT_KEY_ID: u8 = 0x23;
+
+// Why and when a key was revoked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revocation {
+    pub reason: Option<String>,
+    pub revoked_at: DateTime<Utc>,
+}
+
+// A certificate serial revoked by the CA that issued it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevokedSerial {
+    pub serial: u64,
+    pub reason: Option<String>,
+    pub revoked_at: DateTime<Utc>,
+}
+
+// Certificates revoked for one CA
+#[derive(Debug, Clone, Default)]
+pub struct RevokedCertificates {
+    // Inclusive serial ranges
+    pub serials: Vec<(u64, u64)>,
+    pub key_ids: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Krl {
+    pub version: u64,
+    pub generated: Option<DateTime<Utc>>,
+    pub comment: String,
+    // Keyed by the CA's public key blob; an empty blob matches any CA
+    pub certificates: BTreeMap<Vec<u8>, RevokedCertificates>,
+    // Public key blobs
+    pub keys: BTreeSet<Vec<u8>>,
+    pub sha1_fingerprints: BTreeSet<Vec<u8>>,
+    pub sha256_fingerprints: BTreeSet<Vec<u8>>,
+}
+
+impl Krl {
+    // Why the key or certificate is revoked, or None if it isn't
+    pub fn check(&self, key: &PublicKey) -> Option<String> {
+        if self.is_key_revoked(&key.blob) {
+            return Some("the key is revoked".to_string());
+        }
+
+        let certificate = key.certificate.as_ref()?;
+        let ca_key = certificate.ca_key_blob()?;
+        if self.is_key_revoked(&ca_key) {
+            return Some(format!("the CA that signed it ({}) is revoked", certificate.ca_fingerprint));
+        }
+
+        [ca_key.as_slice(), &[]].iter()
+            .filter_map(|ca_key| self.certificates.get(*ca_key))
+            .find_map(|revoked| {
+                if revoked.serials.iter().any(|&(low, high)| (low..=high).contains(&certificate.serial)) {
+                    Some(format!("certificate serial {} is revoked", certificate.serial))
+                } else if revoked.key_ids.contains(&certificate.key_id) {
+                    Some(format!("certificate key ID \"{}\" is revoked", certificate.key_id))
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn is_key_revoked(&self, blob: &[u8]) -> bool {
+        self.keys.contains(blob)
+            || self.sha1_fingerprints.contains(Sha1::digest(blob).as_slice())
+            || self.sha256_fingerprints.contains(Sha256::digest(blob).as_slice())
+    }
+
+    // Encode in the binary format read by sshd and `ssh-keygen -Q`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(KRL_MAGIC);
+        put_u32(&mut out, KRL_FORMAT_VERSION);
+        put_u64(&mut out, self.version);
+        put_u64(&mut out, self.generated.map_or(0, |t| t.timestamp().max(0) as u64));
+        put_u64(&mut out, 0);
+        put_string(&mut out, b"");
+        put_string(&mut out, self.comment.as_bytes());
+
+        for (ca_key, revoked) in &self.certificates {
+            let mut section = Vec::new();
+            put_string(&mut section, ca_key);
+            put_string(&mut section, b"");
+
+            let (singles, ranges): (Vec<_>, Vec<_>) = revoked.serials.iter().copied().partition(|(low, high)| low == high);
+            if !singles.is_empty() {
+                let mut list = Vec::new();
+                for (serial, _) in singles {
+                    put_u64(&mut list, serial);
+                }
+                section.push(CERT_SERIAL_LIST);
+                put_string(&mut section, &list);
+            }
+            for (low, high) in ranges {
+                let mut range = Vec::new();
+                put_u64(&mut range, low);
+                put_u64(&mut range, high);
+                section.push(CERT_SERIAL_RANGE);
+                put_string(&mut section, &range);
+            }
+            if !revoked.key_ids.is_empty() {
+                let mut ids = Vec::new();
+                for key_id in &revoked.key_ids {
+                    put_string(&mut ids, key_id.as_bytes());
+                }
+                section.push(CERT_KEY_ID);
+                put_string(&mut section, &ids);
+            }
+
+            out.push(SECTION_CERTIFICATES);
+            put_string(&mut out, &section);
+        }
+
+        for (section_type, blobs) in [
+            (SECTION_EXPLICIT_KEY, &self.keys),
+            (SECTION_FINGERPRINT_SHA1, &self.sha1_fingerprints),
+            (SECTION_FINGERPRINT_SHA256, &self.sha256_fingerprints),
+        ] {
+            if blobs.is_empty() {
+                continue;
+            }
+            let mut section = Vec::new();
+            for blob in blobs {
+                put_string(&mut section, blob);
+            }
+            out.push(section_type);
+            put_string(&mut out, &section);
+        }
+
+        out
+    }
+}
+
+// Revoke serials, merging them into sorted, non-overlapping ranges
+pub fn add_serials(revoked: &mut RevokedCertificates, serials: impl IntoIterator<Item = (u64, u64)>) {
+    revoked.serials.extend(serials);
+    revoked.serials.sort_unstable();
+
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    for (low, high) in revoked.serials.drain(..) {
+        match merged.last_mut() {
+            Some(last) if low <= last.1.saturating_add(1) => last.1 = last.1.max(high),
+            _ => merged.push((low, high)),
+        }
+    }
+    revoked.serials = merged;
+}
+
+// Read a KRL file. Signatures are skipped, as sshd does.
+pub fn parse(bytes: &[u8]) -> Result<Krl, String> {
+    let data = bytes.strip_prefix(KRL_MAGIC.as_slice())
+        .ok_or("Not an OpenSSH KRL file")?;
+
+    read_krl(data).map_err(|e| if e.starts_with("Invalid SSH public key") {
+        "Invalid KRL file: it is truncated or corrupted".to_string()
+    } else {
+        format!("Invalid KRL file: {}", e)
+    })
+}
+
+fn read_krl(data: &[u8]) -> Result<Krl, String> {
+    let mut reader = WireReader::new(data);
+    let format_version = reader.read_u32()?;
+    if format_version != KRL_FORMAT_VERSION {
+        return Err(format!("unsupported KRL format version {}", format_version));
+    }
+
+    let mut krl = Krl {
+        version: reader.read_u64()?,
+        generated: i64::try_from(reader.read_u64()?).ok()
+            .filter(|&seconds| seconds > 0)
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+        ..Krl::default()
+    };
+    let _flags = reader.read_u64()?;
+    let _reserved = reader.read_bytes()?;
+    krl.comment = reader.read_string()?;
+
+    while !reader.is_empty() {
+        let section_type = reader.read_u8()?;
+        let section = reader.read_bytes()?;
+        match section_type {
+            SECTION_CERTIFICATES => read_certificates_section(&mut krl, section)?,
+            SECTION_EXPLICIT_KEY => read_blobs(section, &mut krl.keys)?,
+            SECTION_FINGERPRINT_SHA1 => read_blobs(section, &mut krl.sha1_fingerprints)?,
+            SECTION_FINGERPRINT_SHA256 => read_blobs(section, &mut krl.sha256_fingerprints)?,
+            SECTION_SIGNATURE => break,
+            other => return Err(format!("unknown KRL section {}", other)),
+        }
+    }
+
+    Ok(krl)
+}
+
+fn read_certificates_section(krl: &mut Krl, section: &[u8]) -> Result<(), String> {
+    let mut reader = WireReader::new(section);
+    let ca_key = reader.read_bytes()?.to_vec();
+    let _reserved = reader.read_bytes()?;
+    let revoked = krl.certificates.entry(ca_key).or_default();
+
+    let mut serials = Vec::new();
+    while !reader.is_empty() {
+        let subsection_type = reader.read_u8()?;
+        let mut subsection = WireReader::new(reader.read_bytes()?);
+        match subsection_type {
+            CERT_SERIAL_LIST => {
+                while !subsection.is_empty() {
+                    let serial = subsection.read_u64()?;
+                    serials.push((serial, serial));
+                }
+            }
+            CERT_SERIAL_RANGE => serials.push((subsection.read_u64()?, subsection.read_u64()?)),
+            CERT_SERIAL_BITMAP => {
+                let offset = subsection.read_u64()?;
+                let bitmap = subsection.read_bytes()?;
+                for (index, byte) in bitmap.iter().rev().enumerate() {
+                    for bit in (0..8).filter(|bit| byte & (1 << bit) != 0) {
+                        let serial = offset.saturating_add(index as u64 * 8 + bit);
+                        serials.push((serial, serial));
+                    }
+                }
+            }
+            CERT_KEY_ID => {
+                while !subsection.is_empty() {
+                    revoked.key_ids.insert(subsection.read_string()?);
+                }
+            }
+            other => return Err(format!("unknown KRL certificate section {}", other)),
+        }
+    }
+
+    add_serials(revoked, serials);
+    Ok(())
+}
+
+fn read_blobs(section: &[u8], blobs: &mut BTreeSet<Vec<u8>>) -> Result<(), String> {
+    let mut reader = WireReader::new(section);
+    while !reader.is_empty() {
+        blobs.insert(reader.read_bytes()?.to_vec());
+    }
+    Ok(())
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+    put_u32(out, data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::public_key;
+
+    // KRLs written by `ssh-keygen -k` for the CA in fixtures/keys, which
+    // stores sparse serials as bitmaps: 30 to 90 in steps of 3, and 40 to 100
+    // in steps of 4
+    const BITMAP_KRL: &[u8] = include_bytes!("../tests/fixtures/krl/bitmap.krl");
+    const BITMAP_KRL_WITHOUT_42: &[u8] = include_bytes!("../tests/fixtures/krl/bitmap_without_42.krl");
+
+    fn key(line: &str) -> PublicKey {
+        public_key::parse(line).unwrap()
+    }
+
+    fn ca() -> PublicKey {
+        key(include_str!("../tests/fixtures/keys/ca.pub"))
+    }
+
+    // Certificate with serial 42 and key ID "alice@example.com", signed by ca()
+    fn certificate() -> PublicKey {
+        key(include_str!("../tests/fixtures/keys/id_ed25519-cert.pub"))
+    }
+
+    #[test]
+    fn to_bytes_then_parse_round_trips() {
+        let rsa = key(include_str!("../tests/fixtures/keys/id_rsa.pub"));
+        let dsa = key(include_str!("../tests/fixtures/keys/id_dsa.pub"));
+        let ecdsa = key(include_str!("../tests/fixtures/keys/id_ecdsa_256.pub"));
+
+        let mut krl = Krl {
+            version: 7,
+            generated: DateTime::from_timestamp(1_735_689_600, 0),
+            comment: "revoked keys".to_string(),
+            ..Krl::default()
+        };
+        let revoked = krl.certificates.entry(ca().blob).or_default();
+        add_serials(revoked, [(1, 1), (42, 42), (100, 199), (u64::MAX, u64::MAX)]);
+        revoked.key_ids.insert("alice@example.com".to_string());
+        revoked.key_ids.insert("bob@example.com".to_string());
+        // Any CA
+        krl.certificates.entry(Vec::new()).or_default()
+            .key_ids.insert("mallory@example.com".to_string());
+        krl.keys.insert(rsa.blob.clone());
+        krl.sha1_fingerprints.insert(Sha1::digest(&dsa.blob).to_vec());
+        krl.sha256_fingerprints.insert(Sha256::digest(&ecdsa.blob).to_vec());
+
+        let parsed = parse(&krl.to_bytes()).unwrap();
+        assert_eq!(parsed.version, 7);
+        assert_eq!(parsed.generated, krl.generated);
+        assert_eq!(parsed.comment, "revoked keys");
+        assert_eq!(parsed.certificates.len(), 2);
+        for (ca_key, revoked) in &krl.certificates {
+            let parsed = &parsed.certificates[ca_key];
+            assert_eq!(parsed.serials, revoked.serials);
+            assert_eq!(parsed.key_ids, revoked.key_ids);
+        }
+        assert_eq!(parsed.keys, krl.keys);
+        assert_eq!(parsed.sha1_fingerprints, krl.sha1_fingerprints);
+        assert_eq!(parsed.sha256_fingerprints, krl.sha256_fingerprints);
+
+        assert_eq!(parsed.check(&rsa).as_deref(), Some("the key is revoked"));
+        assert_eq!(parsed.check(&dsa).as_deref(), Some("the key is revoked"));
+        assert_eq!(parsed.check(&ecdsa).as_deref(), Some("the key is revoked"));
+        assert_eq!(parsed.check(&certificate()).as_deref(), Some("certificate serial 42 is revoked"));
+        assert_eq!(parsed.check(&ca()), None);
+    }
+
+    #[test]
+    fn reads_ssh_keygen_bitmap_sections() {
+        let krl = parse(BITMAP_KRL).unwrap();
+        let expected: Vec<(u64, u64)> = (30..=90).step_by(3).map(|serial| (serial, serial)).collect();
+        assert_eq!(krl.certificates[&ca().blob].serials, expected);
+        assert_eq!(krl.check(&certificate()).as_deref(), Some("certificate serial 42 is revoked"));
+
+        let krl = parse(BITMAP_KRL_WITHOUT_42).unwrap();
+        assert_eq!(krl.certificates[&ca().blob].serials.len(), 16);
+        assert_eq!(krl.check(&certificate()), None);
+    }
+
+    #[test]
+    fn matches_key_ids_and_revoked_cas() {
+        let mut krl = Krl::default();
+        krl.certificates.entry(ca().blob).or_default()
+            .key_ids.insert("alice@example.com".to_string());
+        assert_eq!(krl.check(&certificate()).as_deref(), Some("certificate key ID \"alice@example.com\" is revoked"));
+
+        let mut krl = Krl::default();
+        krl.keys.insert(ca().blob);
+        assert!(krl.check(&certificate()).unwrap().starts_with("the CA that signed it"));
+    }
+
+    #[test]
+    fn add_serials_merges_ranges() {
+        let mut revoked = RevokedCertificates::default();
+        add_serials(&mut revoked, [(10, 10), (5, 5), (6, 6)]);
+        assert_eq!(revoked.serials, [(5, 6), (10, 10)]);
+
+        // Overlapping, adjacent and contained ranges
+        add_serials(&mut revoked, [(100, 200), (150, 250), (251, 260), (120, 130), (7, 9)]);
+        assert_eq!(revoked.serials, [(5, 10), (100, 260)]);
+
+        // Duplicates, and ranges reaching the largest serial
+        add_serials(&mut revoked, [(5, 5), (u64::MAX - 1, u64::MAX), (u64::MAX, u64::MAX)]);
+        assert_eq!(revoked.serials, [(5, 10), (100, 260), (u64::MAX - 1, u64::MAX)]);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(parse(b"not a krl").is_err());
+        let truncated = &BITMAP_KRL[..BITMAP_KRL.len() - 4];
+        assert_eq!(parse(truncated).unwrap_err(), "Invalid KRL file: it is truncated or corrupted");
+    }
+}
@@ -3,13 +3,52 @@ use std::env;
 use chrono::{DateTime, Utc};
 use tauri_plugin_opener::init as init_opener;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SshKey {
     pub id: String,
     pub name: String,
     pub tag: Option<String>,
     pub key: String,
     pub key_type: String,
+    // Parsed from the key; filled in on load for keys saved before these existed
+    #[serde(default)]
+    pub algorithm: String,
+    #[serde(default)]
+    pub bits: Option<u32>,
+    #[serde(default)]
+    pub curve: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    // "SHA256:<base64>" and "MD5:<hex pairs>", computed from the key blob
+    #[serde(default)]
+    pub fingerprint: String,
+    #[serde(default)]
+    pub fingerprint_md5: String,
+    // Set for OpenSSH certificates
+    #[serde(default)]
+    pub certificate: Option<public_key::Certificate>,
+    // Set for FIDO/U2F security key (sk-*) keys, which are hardware-backed
+    #[serde(default)]
+    pub security_key: Option<public_key::SecurityKey>,
+    // OpenSSH private key, kept inside the encrypted vault only. Commands
+    // strip it before returning keys to the frontend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    // Whether a private key is stored and whether it has its own passphrase;
+    // these stay when the private key itself is stripped
+    #[serde(default)]
+    pub has_private_key: bool,
+    #[serde(default)]
+    pub private_key_encrypted: bool,
+    // The private key signs certificates
+    #[serde(default)]
+    pub is_ca: bool,
+    // Set when the key or certificate has been revoked
+    #[serde(default)]
+    pub revoked: Option<krl::Revocation>,
+    // Serials of certificates revoked by this key as a CA
+    #[serde(default)]
+    pub revoked_serials: Vec<krl::RevokedSerial>,
     pub created: DateTime<Utc>,
     pub last_modified: DateTime<Utc>,
 }
@@ -26,9 +65,38 @@ pub struct SshKeyLocation {
     pub path: String,
     pub exists: bool,
     pub keys: Vec<String>,
+    // Public key files whose private key next to them is a different key
+    pub mismatched_keys: Vec<String>,
 }
 
+// A key file read for adding
+#[derive(Debug, Serialize)]
+pub struct SshKeyFile {
+    // The public key: the file's contents, or the public key derived from a
+    // private key
+    pub content: String,
+    // Set if the file is a private key
+    pub private_key_format: Option<private_key::PrivateKeyFormat>,
+    // For a public key file with a private key next to it, whether the two
+    // are a pair
+    pub matches_private_key: Option<bool>,
+}
+
+mod backup;
+mod ca;
 mod commands;
+mod fs_util;
+mod key_format;
+mod krl;
+mod machine;
+mod merge;
+mod ppk;
+mod private_key;
+mod public_key;
+mod settings;
+mod strength;
+mod vault;
+mod watcher;
 
 use commands::*;
 
@@ -36,6 +104,10 @@ use commands::*;
 pub fn run() {
     tauri::Builder::default()
         .plugin(init_opener())
+        .setup(|app| {
+            watcher::start(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_ssh_keys,
             add_ssh_key,
@@ -57,11 +129,48 @@ pub fn run() {
             open_file_dialog,
             open_save_dialog,
             merge_keys_from_file,
+            preview_merge,
+            apply_merge,
             set_encryption_password,
             clear_encryption_password,
             export_keys_with_password,
             import_keys_with_password,
-            get_encryption_mode
+            get_encryption_mode,
+            unlock_vault,
+            lock_vault,
+            recover_vault_with_machine_name,
+            is_vault_locked,
+            get_auto_lock_timeout,
+            set_auto_lock_timeout,
+            list_backups,
+            preview_backup,
+            restore_backup,
+            get_backup_limit,
+            set_backup_limit,
+            get_password_kdf_settings,
+            set_password_kdf_settings,
+            get_key_randomart,
+            get_key_strength_report,
+            get_key_policy,
+            set_key_policy,
+            list_certificates,
+            set_certificate_authority,
+            remove_certificate_authority,
+            sign_certificate,
+            revoke_key,
+            unrevoke_key,
+            revoke_certificate_serial,
+            generate_krl,
+            check_krl,
+            check_keys_against_krl,
+            generate_ssh_key,
+            import_private_key,
+            reveal_private_key,
+            export_private_key,
+            remove_private_key,
+            change_private_key_passphrase,
+            export_public_key,
+            convert_private_key_file
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
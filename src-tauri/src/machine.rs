@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use rand::Rng;
+use sha2::{Sha256, Digest};
+use crate::fs_util;
+
+// Random per-install secret mixed into the machine key, stored in the app data dir
+const INSTALL_SECRET_FILE: &str = "install.secret";
+const INSTALL_SECRET_LEN: usize = 32;
+
+// Fallback name used by versions that identified the machine by hostname
+const LEGACY_UNKNOWN_MACHINE: &str = "unknown-machine";
+
+// Derive the machine-specific encryption key from the platform machine ID and
+// the per-install secret. Neither depends on the hostname, so renaming the
+// machine doesn't change the key.
+pub fn derive_machine_key(app_dir: &Path) -> Result<[u8; 32], String> {
+    let install_secret = load_or_create_install_secret(app_dir)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(get_platform_machine_id().unwrap_or_default().as_bytes());
+    hasher.update(install_secret);
+    hasher.update(b"ssh-kim-machine-key-v2");
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&hasher.finalize());
+    Ok(key)
+}
+
+// Derive the key that older versions used for a given machine name
+pub fn derive_legacy_machine_key(machine_name: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(machine_name.as_bytes());
+    hasher.update(b"ssh-kim-machine-key");
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&hasher.finalize());
+    key
+}
+
+// Machine names older versions may have derived this machine's key from
+pub fn legacy_machine_names() -> Vec<String> {
+    let mut names = Vec::new();
+
+    if let Ok(hostname) = env::var("HOSTNAME") {
+        names.push(hostname);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(output) = std::process::Command::new("scutil")
+            .arg("--get")
+            .arg("ComputerName")
+            .output() {
+            if let Ok(name) = String::from_utf8(output.stdout) {
+                names.push(name.trim().to_string());
+            }
+        }
+    }
+
+    names.push(LEGACY_UNKNOWN_MACHINE.to_string());
+    names.dedup();
+    names
+}
+
+// Read the stable machine ID provided by the operating system
+fn get_platform_machine_id() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        for path in ["/etc/machine-id", "/var/lib/dbus/machine-id"] {
+            if let Ok(id) = fs::read_to_string(path) {
+                let id = id.trim();
+                if !id.is_empty() {
+                    return Some(id.to_string());
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(output) = std::process::Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines() {
+                if line.contains("IOPlatformUUID") {
+                    if let Some(uuid) = line.split('"').nth(3) {
+                        return Some(uuid.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        if let Ok(output) = std::process::Command::new("reg")
+            .args(["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])
+            .output() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines() {
+                if line.contains("MachineGuid") {
+                    if let Some(guid) = line.split_whitespace().last() {
+                        return Some(guid.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Load the per-install secret, creating it on first run
+fn load_or_create_install_secret(app_dir: &Path) -> Result<Vec<u8>, String> {
+    let secret_path = app_dir.join(INSTALL_SECRET_FILE);
+
+    match fs::read_to_string(&secret_path) {
+        Ok(content) => return decode_install_secret(&content, &secret_path),
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            return Err(format!("Failed to read install secret {}: {}", secret_path.display(), e));
+        }
+        Err(_) => {}
+    }
+
+    fs_util::create_private_dir(app_dir)
+        .map_err(|e| format!("Failed to create {}: {}", app_dir.display(), e))?;
+
+    let secret: [u8; INSTALL_SECRET_LEN] = rand::thread_rng().gen();
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    match options.open(&secret_path) {
+        Ok(mut file) => {
+            file.write_all(hex::encode(secret).as_bytes())
+                .and_then(|_| file.sync_all())
+                .map_err(|e| format!("Failed to write install secret: {}", e))?;
+            Ok(secret.to_vec())
+        }
+        // Another instance created it first; use theirs
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            let content = fs::read_to_string(&secret_path)
+                .map_err(|e| format!("Failed to read install secret: {}", e))?;
+            decode_install_secret(&content, &secret_path)
+        }
+        Err(e) => Err(format!("Failed to create install secret: {}", e)),
+    }
+}
+
+fn decode_install_secret(content: &str, secret_path: &Path) -> Result<Vec<u8>, String> {
+    match hex::decode(content.trim()) {
+        Ok(secret) if secret.len() == INSTALL_SECRET_LEN => Ok(secret),
+        _ => Err(format!("Install secret {} is corrupted", secret_path.display())),
+    }
+}
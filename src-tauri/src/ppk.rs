@@ -0,0 +1,554 @@
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine as _, engine::general_purpose};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use ssh_key::private::{EcdsaKeypair, Ed25519Keypair, KeypairData, RsaKeypair, RsaPrivateKey};
+use ssh_key::public::{EcdsaPublicKey, KeyData};
+use ssh_key::{Mpint, PrivateKey};
+use zeroize::Zeroizing;
+use crate::private_key;
+use crate::public_key::WireReader;
+use crate::vault::{MAX_ARGON2_ITERATIONS, MAX_ARGON2_MEMORY_KIB, MAX_ARGON2_PARALLELISM};
+
+// PuTTY private key files (.ppk), versions 2 and 3, as described in
+// appendix C of the PuTTY manual
+const HEADER_PREFIX: &str = "PuTTY-User-Key-File-";
+const V2_MAC_KEY_PREFIX: &[u8] = b"putty-private-key-file-mac-key";
+const ENCRYPTION_NONE: &str = "none";
+const ENCRYPTION_AES256_CBC: &str = "aes256-cbc";
+const AES_BLOCK_LEN: usize = 16;
+// Base64 line length used by PuTTYgen
+const LINE_LENGTH: usize = 64;
+
+// Argon2id cost for keys written here: PuTTYgen's default memory and lanes,
+// with a fixed number of passes rather than a timed one
+const ARGON2_MEMORY_KIB: u32 = 8192;
+const ARGON2_PASSES: u32 = 13;
+const ARGON2_PARALLELISM: u32 = 1;
+const ARGON2_SALT_LEN: usize = 16;
+
+// A parsed .ppk file. The public key is always readable; the private key is
+// decrypted on demand.
+#[derive(Debug, Clone)]
+pub struct PuttyKey {
+    pub version: u8,
+    pub algorithm: String,
+    pub encryption: String,
+    pub comment: String,
+    pub public_blob: Vec<u8>,
+    private_blob: Vec<u8>,
+    // Argon2 variant, memory, passes, parallelism and salt (version 3 only)
+    argon2: Option<(Algorithm, u32, u32, u32, Vec<u8>)>,
+    mac: Vec<u8>,
+}
+
+pub fn is_ppk(text: &str) -> bool {
+    text.trim_start().starts_with(HEADER_PREFIX)
+}
+
+pub fn parse(text: &str) -> Result<PuttyKey, String> {
+    // Values run to the end of the line; comments may end in spaces
+    let mut lines = text.trim().lines().map(|line| line.trim_end_matches('\r'));
+    let header = lines.next().unwrap_or_default();
+    let (version, algorithm) = header.strip_prefix(HEADER_PREFIX)
+        .and_then(|rest| rest.split_once(": "))
+        .ok_or("Not a PuTTY private key file")?;
+    let version = match version {
+        "2" => 2,
+        "3" => 3,
+        other => return Err(format!("Unsupported PuTTY key file version {}", other)),
+    };
+
+    let mut fields = std::collections::HashMap::new();
+    let mut public_blob = None;
+    let mut private_blob = None;
+    while let Some(line) = lines.next() {
+        let (name, value) = line.split_once(':')
+            .ok_or_else(|| format!("Invalid PuTTY key file: unexpected line '{}'", line))?;
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        if let Some(kind) = name.strip_suffix("-Lines") {
+            let count: usize = value.parse()
+                .map_err(|_| format!("Invalid PuTTY key file: bad {} count", name))?;
+            let mut encoded = String::new();
+            for _ in 0..count {
+                encoded += lines.next().ok_or("Invalid PuTTY key file: it is truncated")?;
+            }
+            let blob = general_purpose::STANDARD.decode(encoded.trim())
+                .map_err(|_| format!("Invalid PuTTY key file: the {} key is not valid base64", kind.to_lowercase()))?;
+            match kind {
+                "Public" => public_blob = Some(blob),
+                "Private" => private_blob = Some(blob),
+                _ => return Err(format!("Invalid PuTTY key file: unknown field {}", name)),
+            }
+        } else {
+            fields.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    let field = |name: &str| fields.get(name).cloned()
+        .ok_or_else(|| format!("Invalid PuTTY key file: missing {}", name));
+    let encryption = field("Encryption")?;
+    if encryption != ENCRYPTION_NONE && encryption != ENCRYPTION_AES256_CBC {
+        return Err(format!("Unsupported PuTTY key encryption '{}'", encryption));
+    }
+
+    let argon2 = if version == 3 && encryption != ENCRYPTION_NONE {
+        let variant = match field("Key-Derivation")?.as_str() {
+            "Argon2id" => Algorithm::Argon2id,
+            "Argon2i" => Algorithm::Argon2i,
+            "Argon2d" => Algorithm::Argon2d,
+            other => return Err(format!("Unsupported PuTTY key derivation '{}'", other)),
+        };
+        let number = |name: &str| field(name)?.parse::<u32>()
+            .map_err(|_| format!("Invalid PuTTY key file: bad {}", name));
+        let salt = hex::decode(field("Argon2-Salt")?)
+            .map_err(|_| "Invalid PuTTY key file: bad Argon2-Salt".to_string())?;
+        Some((variant, number("Argon2-Memory")?, number("Argon2-Passes")?, number("Argon2-Parallelism")?, salt))
+    } else {
+        None
+    };
+
+    let public_blob = public_blob.ok_or("Invalid PuTTY key file: missing the public key")?;
+    if WireReader::new(&public_blob).read_string()? != algorithm {
+        return Err(format!("Invalid PuTTY key file: the header says '{}' but the public key doesn't", algorithm));
+    }
+
+    Ok(PuttyKey {
+        version,
+        algorithm: algorithm.to_string(),
+        encryption,
+        comment: fields.get("Comment").cloned().unwrap_or_default(),
+        public_blob,
+        private_blob: private_blob.ok_or("Invalid PuTTY key file: missing the private key")?,
+        argon2,
+        mac: hex::decode(field("Private-MAC")?)
+            .map_err(|_| "Invalid PuTTY key file: bad Private-MAC".to_string())?,
+    })
+}
+
+impl PuttyKey {
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption != ENCRYPTION_NONE
+    }
+
+    // The public key as an authorized_keys line
+    pub fn public_key_line(&self) -> String {
+        let line = format!("{} {}", self.algorithm, general_purpose::STANDARD.encode(&self.public_blob));
+        if self.comment.is_empty() {
+            line
+        } else {
+            format!("{} {}", line, self.comment)
+        }
+    }
+
+    // The private key, checked against the file's MAC. Encrypted files need
+    // their passphrase.
+    pub fn decrypt(&self, passphrase: Option<&str>) -> Result<PrivateKey, String> {
+        let passphrase = match (self.is_encrypted(), passphrase) {
+            (true, None) => return Err("The PuTTY key is protected with a passphrase".to_string()),
+            (true, Some(passphrase)) => passphrase,
+            (false, _) => "",
+        };
+
+        let keys = derive_keys(self.version, self.is_encrypted(), passphrase, self.argon2.as_ref())?;
+        let mut private_blob = Zeroizing::new(self.private_blob.clone());
+        if self.is_encrypted() {
+            cbc_decrypt(&keys.cipher_key, &keys.iv, &mut private_blob)?;
+        }
+
+        let mac = compute_mac(self.version, &keys.mac_key, &self.algorithm, &self.encryption, &self.comment, &self.public_blob, &private_blob);
+        if mac.len() != self.mac.len() || !constant_time_eq(&mac, &self.mac) {
+            return Err(if self.is_encrypted() {
+                "Incorrect passphrase for the PuTTY key".to_string()
+            } else {
+                "Invalid PuTTY key file: the MAC doesn't match, so it is corrupted or has been modified".to_string()
+            });
+        }
+
+        let keypair = read_keypair(&self.public_blob, &private_blob)?;
+        PrivateKey::new(keypair, &self.comment)
+            .map_err(|e| format!("Invalid PuTTY key: {}", e))
+    }
+}
+
+// Convert a .ppk file to OpenSSH format, keeping its passphrase. The
+// passphrase is ignored for unencrypted files.
+pub fn to_openssh(text: &str, passphrase: Option<&str>) -> Result<String, String> {
+    let putty_key = parse(text)?;
+    let passphrase = passphrase.filter(|_| putty_key.is_encrypted());
+    private_key::encode(&putty_key.decrypt(passphrase)?, passphrase, private_key::DEFAULT_KDF_ROUNDS)
+}
+
+// Write a (decrypted) private key as a version 3 .ppk file, encrypted with
+// aes256-cbc and Argon2id if a passphrase is given
+pub fn encode(private_key: &PrivateKey, passphrase: Option<&str>) -> Result<String, String> {
+    let algorithm = private_key.algorithm().as_str().to_string();
+    let public_blob = private_key.public_key().to_bytes()
+        .map_err(|e| format!("Failed to encode public key: {}", e))?;
+    let mut private_blob = Zeroizing::new(private_blob(private_key.key_data())?);
+    let comment = private_key.comment().to_string();
+
+    let (encryption, argon2) = match passphrase {
+        Some(_) => {
+            let mut salt = vec![0u8; ARGON2_SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            (ENCRYPTION_AES256_CBC, Some((Algorithm::Argon2id, ARGON2_MEMORY_KIB, ARGON2_PASSES, ARGON2_PARALLELISM, salt)))
+        }
+        None => (ENCRYPTION_NONE, None),
+    };
+
+    let keys = derive_keys(3, passphrase.is_some(), passphrase.unwrap_or_default(), argon2.as_ref())?;
+    if passphrase.is_some() {
+        // Random padding up to the cipher block size, as PuTTYgen does
+        let padding = (AES_BLOCK_LEN - private_blob.len() % AES_BLOCK_LEN) % AES_BLOCK_LEN;
+        let mut random = vec![0u8; padding];
+        OsRng.fill_bytes(&mut random);
+        private_blob.extend_from_slice(&random);
+    }
+    let mac = compute_mac(3, &keys.mac_key, &algorithm, encryption, &comment, &public_blob, &private_blob);
+    if passphrase.is_some() {
+        cbc_encrypt(&keys.cipher_key, &keys.iv, &mut private_blob)?;
+    }
+
+    let mut out = format!("{}3: {}\n", HEADER_PREFIX, algorithm);
+    out += &format!("Encryption: {}\n", encryption);
+    out += &format!("Comment: {}\n", comment);
+    push_lines(&mut out, "Public", &public_blob);
+    if let Some((_, memory, passes, parallelism, salt)) = &argon2 {
+        out += "Key-Derivation: Argon2id\n";
+        out += &format!("Argon2-Memory: {}\n", memory);
+        out += &format!("Argon2-Passes: {}\n", passes);
+        out += &format!("Argon2-Parallelism: {}\n", parallelism);
+        out += &format!("Argon2-Salt: {}\n", hex::encode(salt));
+    }
+    push_lines(&mut out, "Private", &private_blob);
+    out += &format!("Private-MAC: {}\n", hex::encode(mac));
+    Ok(out)
+}
+
+fn push_lines(out: &mut String, kind: &str, blob: &[u8]) {
+    let encoded = general_purpose::STANDARD.encode(blob);
+    let lines: Vec<&[u8]> = encoded.as_bytes().chunks(LINE_LENGTH).collect();
+    *out += &format!("{}-Lines: {}\n", kind, lines.len());
+    for line in lines {
+        *out += &String::from_utf8_lossy(line);
+        out.push('\n');
+    }
+}
+
+struct DerivedKeys {
+    cipher_key: Zeroizing<Vec<u8>>,
+    iv: [u8; AES_BLOCK_LEN],
+    mac_key: Zeroizing<Vec<u8>>,
+}
+
+// Version 2 derives the AES key from SHA-1 of the passphrase (with a zero IV)
+// and the MAC key from SHA-1 of a fixed string and the passphrase. Version 3
+// takes the AES key, IV and MAC key from one Argon2 output, and an
+// unencrypted key has an empty MAC key.
+fn derive_keys(version: u8, encrypted: bool, passphrase: &str, argon2: Option<&(Algorithm, u32, u32, u32, Vec<u8>)>) -> Result<DerivedKeys, String> {
+    if version == 2 {
+        let mut cipher_key = Zeroizing::new(Vec::new());
+        for counter in [0u32, 1] {
+            let mut hasher = Sha1::new();
+            hasher.update(counter.to_be_bytes());
+            hasher.update(passphrase.as_bytes());
+            cipher_key.extend_from_slice(&hasher.finalize());
+        }
+        cipher_key.truncate(32);
+
+        let mut hasher = Sha1::new();
+        hasher.update(V2_MAC_KEY_PREFIX);
+        hasher.update(passphrase.as_bytes());
+        return Ok(DerivedKeys {
+            cipher_key,
+            iv: [0; AES_BLOCK_LEN],
+            mac_key: Zeroizing::new(hasher.finalize().to_vec()),
+        });
+    }
+
+    if !encrypted {
+        return Ok(DerivedKeys {
+            cipher_key: Zeroizing::new(Vec::new()),
+            iv: [0; AES_BLOCK_LEN],
+            mac_key: Zeroizing::new(Vec::new()),
+        });
+    }
+
+    let (variant, memory, passes, parallelism, salt) = argon2
+        .ok_or("Invalid PuTTY key file: missing the key derivation parameters")?;
+    // Refuse costs that would exhaust memory or hang the app
+    if *memory > MAX_ARGON2_MEMORY_KIB {
+        return Err(format!("The PuTTY key asks for {} KiB of memory to decrypt, more than the {} KiB allowed", memory, MAX_ARGON2_MEMORY_KIB));
+    }
+    if *passes > MAX_ARGON2_ITERATIONS {
+        return Err(format!("The PuTTY key asks for {} Argon2 passes to decrypt, more than the {} allowed", passes, MAX_ARGON2_ITERATIONS));
+    }
+    if *parallelism > MAX_ARGON2_PARALLELISM {
+        return Err(format!("The PuTTY key asks for {} Argon2 lanes to decrypt, more than the {} allowed", parallelism, MAX_ARGON2_PARALLELISM));
+    }
+    let params = Params::new(*memory, *passes, *parallelism, Some(80))
+        .map_err(|e| format!("Invalid PuTTY key file: bad Argon2 parameters: {}", e))?;
+    let mut output = Zeroizing::new([0u8; 80]);
+    Argon2::new(*variant, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, output.as_mut())
+        .map_err(|e| format!("Failed to derive the PuTTY key: {}", e))?;
+
+    let mut iv = [0u8; AES_BLOCK_LEN];
+    iv.copy_from_slice(&output[32..48]);
+    Ok(DerivedKeys {
+        cipher_key: Zeroizing::new(output[..32].to_vec()),
+        iv,
+        mac_key: Zeroizing::new(output[48..].to_vec()),
+    })
+}
+
+// HMAC (SHA-1 for version 2, SHA-256 for version 3) over the header fields
+// and both blobs, with the private blob decrypted but still padded
+fn compute_mac(version: u8, mac_key: &[u8], algorithm: &str, encryption: &str, comment: &str, public_blob: &[u8], private_blob: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for field in [algorithm.as_bytes(), encryption.as_bytes(), comment.as_bytes(), public_blob, private_blob] {
+        put_string(&mut data, field);
+    }
+
+    if version == 2 {
+        let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(mac_key).expect("HMAC accepts any key length");
+        mac.update(&data);
+        mac.finalize().into_bytes().to_vec()
+    } else {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(mac_key).expect("HMAC accepts any key length");
+        mac.update(&data);
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn cbc_decrypt(key: &[u8], iv: &[u8; AES_BLOCK_LEN], data: &mut [u8]) -> Result<(), String> {
+    if !data.len().is_multiple_of(AES_BLOCK_LEN) {
+        return Err("Invalid PuTTY key file: the private key has a bad length".to_string());
+    }
+    let cipher = Aes256::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+
+    let mut previous = *iv;
+    for chunk in data.chunks_mut(AES_BLOCK_LEN) {
+        let mut ciphertext = [0u8; AES_BLOCK_LEN];
+        ciphertext.copy_from_slice(chunk);
+        cipher.decrypt_block(GenericArray::from_mut_slice(chunk));
+        for (byte, prev) in chunk.iter_mut().zip(previous) {
+            *byte ^= prev;
+        }
+        previous = ciphertext;
+    }
+    Ok(())
+}
+
+fn cbc_encrypt(key: &[u8], iv: &[u8; AES_BLOCK_LEN], data: &mut [u8]) -> Result<(), String> {
+    let cipher = Aes256::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+
+    let mut previous = *iv;
+    for chunk in data.chunks_mut(AES_BLOCK_LEN) {
+        for (byte, prev) in chunk.iter_mut().zip(previous) {
+            *byte ^= prev;
+        }
+        cipher.encrypt_block(GenericArray::from_mut_slice(chunk));
+        previous.copy_from_slice(chunk);
+    }
+    Ok(())
+}
+
+// Private blobs hold only the private fields: d, p, q and iqmp for RSA, the
+// scalar for ECDSA, and the little-endian seed for Ed25519
+fn read_keypair(public_blob: &[u8], private_blob: &[u8]) -> Result<KeypairData, String> {
+    let public = ssh_key::PublicKey::from_bytes(public_blob)
+        .map_err(|e| format!("Invalid PuTTY key: {}", e))?;
+    let mut reader = WireReader::new(private_blob);
+    let invalid = |_| "Invalid PuTTY key: the private key doesn't fit the public key".to_string();
+
+    match public.key_data() {
+        KeyData::Rsa(public) => {
+            let mut mpint = || reader.read_bytes().and_then(|b| Mpint::from_bytes(b).map_err(|e| e.to_string()));
+            let (d, p, q, iqmp) = (mpint()?, mpint()?, mpint()?, mpint()?);
+            Ok(KeypairData::Rsa(RsaKeypair {
+                public: public.clone(),
+                private: RsaPrivateKey { d, iqmp, p, q },
+            }))
+        }
+        KeyData::Ed25519(public) => {
+            let bytes = reader.read_bytes()?;
+            if bytes.len() > 32 {
+                return Err("Invalid PuTTY key: the Ed25519 private key is too long".to_string());
+            }
+            let mut seed = Zeroizing::new([0u8; 32]);
+            seed[..bytes.len()].copy_from_slice(bytes);
+            let keypair = Ed25519Keypair::from_seed(&seed);
+            if keypair.public != *public {
+                return Err("Invalid PuTTY key: the private key doesn't fit the public key".to_string());
+            }
+            Ok(KeypairData::Ed25519(keypair))
+        }
+        KeyData::Ecdsa(public) => {
+            let scalar = reader.read_bytes()?;
+            let scalar = scalar.strip_prefix(&[0]).unwrap_or(scalar);
+            let keypair = match public {
+                EcdsaPublicKey::NistP256(point) => EcdsaKeypair::NistP256 {
+                    public: *point,
+                    private: p256::SecretKey::from_slice(scalar).map_err(invalid)?.into(),
+                },
+                EcdsaPublicKey::NistP384(point) => EcdsaKeypair::NistP384 {
+                    public: *point,
+                    private: p384::SecretKey::from_slice(scalar).map_err(invalid)?.into(),
+                },
+                EcdsaPublicKey::NistP521(point) => EcdsaKeypair::NistP521 {
+                    public: *point,
+                    private: p521::SecretKey::from_slice(scalar).map_err(invalid)?.into(),
+                },
+            };
+            Ok(KeypairData::Ecdsa(keypair))
+        }
+        _ => Err(format!("Unsupported PuTTY key type '{}', expected RSA, ECDSA or Ed25519", public.algorithm())),
+    }
+}
+
+fn private_blob(keypair: &KeypairData) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    match keypair {
+        KeypairData::Rsa(keypair) => {
+            for mpint in [&keypair.private.d, &keypair.private.p, &keypair.private.q, &keypair.private.iqmp] {
+                put_string(&mut out, mpint.as_bytes());
+            }
+        }
+        KeypairData::Ed25519(keypair) => {
+            // PuTTY writes the seed as a little-endian integer, without
+            // trailing zero bytes
+            let seed = keypair.private.to_bytes();
+            let len = seed.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+            put_string(&mut out, &seed[..len]);
+        }
+        KeypairData::Ecdsa(keypair) => {
+            let scalar = Mpint::from_positive_bytes(keypair.private_key_bytes())
+                .map_err(|e| format!("Failed to encode private key: {}", e))?;
+            put_string(&mut out, scalar.as_bytes());
+        }
+        _ => return Err("Only RSA, ECDSA and Ed25519 keys can be converted to PuTTY format".to_string()),
+    }
+    Ok(out)
+}
+
+fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keys written by PuTTYgen, protected with the passphrase "123" where
+    // encrypted, next to the same keys in OpenSSH format
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!("../tests/fixtures/ppk/", $name))
+        };
+    }
+    const PASSPHRASE: &str = "123";
+    const FIXTURES: &[(&str, &str, &str, &str)] = &[
+        ("v3 ed25519", fixture!("id_ed25519.ppk"), fixture!("id_ed25519"), fixture!("id_ed25519.pub")),
+        ("v3 ed25519, encrypted", fixture!("id_ed25519_enc.ppk"), fixture!("id_ed25519"), fixture!("id_ed25519.pub")),
+        ("v3 ecdsa", fixture!("id_ecdsa_p256.ppk"), fixture!("id_ecdsa_p256"), fixture!("id_ecdsa_p256.pub")),
+        ("v3 ecdsa, encrypted", fixture!("id_ecdsa_p256_enc.ppk"), fixture!("id_ecdsa_p256"), fixture!("id_ecdsa_p256.pub")),
+        ("v3 rsa", fixture!("id_rsa_3072.ppk"), fixture!("id_rsa_3072"), fixture!("id_rsa_3072.pub")),
+        ("v3 rsa, encrypted", fixture!("id_rsa_3072_enc.ppk"), fixture!("id_rsa_3072"), fixture!("id_rsa_3072.pub")),
+        ("v2 rsa", fixture!("id_rsa_3072_v2.ppk"), fixture!("id_rsa_3072"), fixture!("id_rsa_3072.pub")),
+        ("v2 rsa, encrypted", fixture!("id_rsa_3072_enc_v2.ppk"), fixture!("id_rsa_3072"), fixture!("id_rsa_3072.pub")),
+    ];
+
+    fn base64_field(line: &str) -> Option<&str> {
+        line.split_whitespace().nth(1)
+    }
+
+    #[test]
+    fn parses_puttygen_keys() {
+        for (name, ppk_text, _, public_key) in FIXTURES {
+            let key = parse(ppk_text).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(key.version, if name.starts_with("v2") { 2 } else { 3 }, "{}", name);
+            assert_eq!(key.is_encrypted(), name.ends_with("encrypted"), "{}", name);
+            assert_eq!(key.comment, "user@example.com", "{}", name);
+            assert_eq!(base64_field(&key.public_key_line()), base64_field(public_key), "{}", name);
+        }
+
+        // Windows line endings
+        assert!(parse(&fixture!("id_ed25519.ppk").replace('\n', "\r\n")).is_ok());
+        assert!(parse(fixture!("id_ed25519")).is_err());
+    }
+
+    #[test]
+    fn decrypts_puttygen_keys() {
+        for (name, ppk_text, openssh, _) in FIXTURES {
+            let key = parse(ppk_text).unwrap();
+            let expected = private_key::parse(openssh).unwrap();
+            if key.is_encrypted() {
+                assert!(key.decrypt(None).is_err(), "{}", name);
+                assert!(key.decrypt(Some("wrong")).unwrap_err().contains("Incorrect"), "{}", name);
+            }
+            let decrypted = key.decrypt(Some(PASSPHRASE)).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(decrypted.key_data(), expected.key_data(), "{}", name);
+            assert_eq!(decrypted.comment(), "user@example.com", "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_keys() {
+        let tampered = fixture!("id_ed25519.ppk").replace("Comment: ", "Comment: x");
+        assert!(parse(&tampered).unwrap().decrypt(None).unwrap_err().contains("MAC"));
+    }
+
+    #[test]
+    fn rejects_excessive_argon2_costs() {
+        // Crafted from id_ed25519_enc.ppk; the limits are checked before any
+        // key derivation or MAC check
+        let cases = [
+            (fixture!("argon2_memory.ppk"), "KiB of memory"),
+            (fixture!("argon2_passes.ppk"), "Argon2 passes"),
+            (fixture!("argon2_parallelism.ppk"), "Argon2 lanes"),
+        ];
+        for (ppk_text, expected) in cases {
+            let error = parse(ppk_text).unwrap().decrypt(Some(PASSPHRASE)).unwrap_err();
+            assert!(error.contains(expected), "{}", error);
+        }
+    }
+
+    #[test]
+    fn converts_to_openssh() {
+        for (name, ppk_text, openssh, _) in FIXTURES {
+            let converted = private_key::parse(&to_openssh(ppk_text, Some(PASSPHRASE)).unwrap()).unwrap();
+            // The passphrase is kept
+            assert_eq!(converted.is_encrypted(), name.ends_with("encrypted"), "{}", name);
+            let converted = private_key::unlock(&converted, Some(PASSPHRASE)).unwrap();
+            assert_eq!(converted.key_data(), private_key::parse(openssh).unwrap().key_data(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn encode_then_decrypt_round_trips() {
+        for openssh in [fixture!("id_ed25519"), fixture!("id_ecdsa_p256"), fixture!("id_rsa_3072")] {
+            let key = private_key::parse(openssh).unwrap();
+            for passphrase in [None, Some("s3cret")] {
+                let decoded = parse(&encode(&key, passphrase).unwrap()).unwrap();
+                assert_eq!(decoded.version, 3);
+                assert_eq!(decoded.is_encrypted(), passphrase.is_some());
+                assert_eq!(decoded.comment, key.comment());
+                assert_eq!(decoded.decrypt(passphrase).unwrap().key_data(), key.key_data());
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::fs_util;
+use crate::strength::KeyPolicy;
+use crate::vault::Kdf;
+
+// Number of rolling backups kept for each keys file (0 disables backups)
+pub const DEFAULT_BACKUP_LIMIT: usize = 10;
+// Idle minutes after which an unlocked password-protected vault locks itself
+// (0 disables auto-lock)
+pub const DEFAULT_AUTO_LOCK_MINUTES: u64 = 15;
+
+// App settings that have to survive a restart, kept as JSON next to the
+// default keys file. Fields missing from the file take their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub backup_limit: usize,
+    pub auto_lock_minutes: u64,
+    // What the key strength report checks keys against
+    pub key_policy: KeyPolicy,
+    // Argon2id cost used for newly password-protected files
+    pub password_kdf: Kdf,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            backup_limit: DEFAULT_BACKUP_LIMIT,
+            auto_lock_minutes: DEFAULT_AUTO_LOCK_MINUTES,
+            key_policy: KeyPolicy::default(),
+            password_kdf: Kdf::default_argon2id(),
+        }
+    }
+}
+
+// Read the settings file. A missing file gives the defaults; an unreadable
+// one is reported and also gives the defaults, so the app still starts.
+pub fn load(path: &Path) -> Settings {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return Settings::default(),
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        println!("Ignoring invalid settings file {}: {}", path.display(), e);
+        Settings::default()
+    })
+}
+
+pub fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs_util::create_private_dir(dir)
+            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    fs_util::write_atomic(path, content.as_bytes())
+        .map_err(|e| format!("Failed to write settings file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{ "backup_limit": 3 }"#).unwrap();
+        assert_eq!(settings, Settings { backup_limit: 3, ..Settings::default() });
+
+        let settings: Settings = serde_json::from_str(r#"{ "key_policy": { "allow_dsa": true } }"#).unwrap();
+        assert!(settings.key_policy.allow_dsa);
+        assert_eq!(settings.key_policy.min_rsa_bits, KeyPolicy::default().min_rsa_bits);
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let dir = std::env::temp_dir().join(format!("ssh-kim-settings-{}", uuid::Uuid::new_v4().simple()));
+        let path = dir.join("settings.json");
+        assert_eq!(load(&path), Settings::default());
+
+        let settings = Settings {
+            auto_lock_minutes: 0,
+            key_policy: KeyPolicy { allow_nist_curves: false, ..KeyPolicy::default() },
+            password_kdf: Kdf::Argon2id { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 },
+            ..Settings::default()
+        };
+        save(&path, &settings).unwrap();
+        let loaded = load(&path);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(loaded, settings);
+    }
+}
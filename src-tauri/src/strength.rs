@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use crate::SshKey;
+
+// RSA keys below this are weak whatever the policy says
+const RSA_BROKEN_BITS: u32 = 2048;
+
+// Which keys are acceptable. Fields missing when it is read take their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyPolicy {
+    // RSA keys below this size are flagged (2048 or 3072 are common choices)
+    pub min_rsa_bits: u32,
+    // DSA is disabled by default since OpenSSH 7.0
+    pub allow_dsa: bool,
+    // ECDSA on the NIST P-256/384/521 curves, including ecdsa-sk keys
+    pub allow_nist_curves: bool,
+    // Keys with any of these tags must be FIDO security keys (sk-*), e.g.
+    // keys that reach production
+    pub require_security_key_tags: Vec<String>,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        KeyPolicy {
+            min_rsa_bits: 3072,
+            allow_dsa: false,
+            allow_nist_curves: true,
+            require_security_key_tags: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrengthLevel {
+    Ok,
+    // Allowed, but not recommended or against the policy
+    Warning,
+    Weak,
+    // The key couldn't be parsed
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyStrength {
+    pub id: String,
+    pub name: String,
+    pub algorithm: String,
+    pub bits: Option<u32>,
+    // FIDO security key, whose private half can't be copied off the hardware
+    pub hardware_backed: bool,
+    pub level: StrengthLevel,
+    pub issues: Vec<String>,
+}
+
+// Assess a key's algorithm and size against the policy
+pub fn assess(key: &SshKey, policy: &KeyPolicy) -> KeyStrength {
+    let mut issues = Vec::new();
+    let bits = key.bits.unwrap_or(0);
+
+    // A certificate is as strong as the key it certifies
+    let mut level = match key.key_type.trim_end_matches("-cert") {
+        "dsa" if policy.allow_dsa => {
+            issues.push("DSA keys are limited to 1024 bits and are disabled by default in OpenSSH".to_string());
+            StrengthLevel::Warning
+        }
+        "dsa" => {
+            issues.push("DSA keys are limited to 1024 bits and are not allowed by the policy".to_string());
+            StrengthLevel::Weak
+        }
+        "rsa" if bits < RSA_BROKEN_BITS => {
+            issues.push(format!("RSA key is only {} bits; keys under {} bits are considered broken", bits, RSA_BROKEN_BITS));
+            StrengthLevel::Weak
+        }
+        "rsa" if bits < policy.min_rsa_bits => {
+            issues.push(format!("RSA key is {} bits; the policy requires at least {}", bits, policy.min_rsa_bits));
+            StrengthLevel::Warning
+        }
+        "ecdsa" | "ecdsa-sk" if !policy.allow_nist_curves => {
+            issues.push(format!(
+                "{} uses the NIST curve {}, which the policy does not allow",
+                key.algorithm,
+                key.curve.as_deref().unwrap_or("unknown")
+            ));
+            StrengthLevel::Warning
+        }
+        "rsa" | "ecdsa" | "ecdsa-sk" | "ed25519" | "ed25519-sk" => StrengthLevel::Ok,
+        _ => {
+            issues.push("The key could not be parsed, so its strength is unknown".to_string());
+            StrengthLevel::Unknown
+        }
+    };
+
+    let required_by_tag = key.tag.as_deref()
+        .filter(|tag| policy.require_security_key_tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim())));
+    if let (Some(tag), None) = (required_by_tag, &key.security_key) {
+        issues.push(format!("The policy requires keys tagged '{}' to be FIDO security keys (sk-*)", tag));
+        if level == StrengthLevel::Ok {
+            level = StrengthLevel::Warning;
+        }
+    }
+
+    KeyStrength {
+        id: key.id.clone(),
+        name: key.name.clone(),
+        algorithm: key.algorithm.clone(),
+        bits: key.bits,
+        hardware_backed: key.security_key.is_some(),
+        level,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::public_key;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!("../tests/fixtures/keys/", $name))
+        };
+    }
+
+    fn key(line: &str, tag: Option<&str>) -> SshKey {
+        let parsed = public_key::parse(line).unwrap();
+        SshKey {
+            id: "1".to_string(),
+            name: "key".to_string(),
+            tag: tag.map(str::to_string),
+            key: line.trim().to_string(),
+            key_type: parsed.key_type,
+            algorithm: parsed.algorithm,
+            bits: parsed.bits,
+            curve: parsed.curve,
+            comment: parsed.comment,
+            fingerprint: String::new(),
+            fingerprint_md5: String::new(),
+            certificate: parsed.certificate,
+            security_key: parsed.security_key,
+            private_key: None,
+            has_private_key: false,
+            private_key_encrypted: false,
+            is_ca: false,
+            revoked: None,
+            revoked_serials: Vec::new(),
+            created: Utc::now(),
+            last_modified: Utc::now(),
+        }
+    }
+
+    fn level(key: &SshKey, policy: &KeyPolicy) -> StrengthLevel {
+        assess(key, policy).level
+    }
+
+    #[test]
+    fn dsa_is_weak_unless_allowed() {
+        let dsa = key(fixture!("id_dsa.pub"), None);
+        assert_eq!(level(&dsa, &KeyPolicy::default()), StrengthLevel::Weak);
+        let policy = KeyPolicy { allow_dsa: true, ..KeyPolicy::default() };
+        assert_eq!(level(&dsa, &policy), StrengthLevel::Warning);
+    }
+
+    #[test]
+    fn rsa_is_checked_against_the_minimum_size() {
+        // 2048 bits: below the default policy of 3072
+        let rsa = key(fixture!("id_rsa.pub"), None);
+        let report = assess(&rsa, &KeyPolicy::default());
+        assert_eq!(report.level, StrengthLevel::Warning);
+        assert!(report.issues[0].contains("at least 3072"), "{:?}", report.issues);
+        let policy = KeyPolicy { min_rsa_bits: 2048, ..KeyPolicy::default() };
+        assert_eq!(level(&rsa, &policy), StrengthLevel::Ok);
+
+        // Below 2048 bits is weak whatever the policy
+        let small = SshKey { bits: Some(1024), ..rsa };
+        let policy = KeyPolicy { min_rsa_bits: 1024, ..KeyPolicy::default() };
+        assert_eq!(level(&small, &policy), StrengthLevel::Weak);
+    }
+
+    #[test]
+    fn nist_curves_follow_the_policy() {
+        let no_nist = KeyPolicy { allow_nist_curves: false, ..KeyPolicy::default() };
+        for line in [fixture!("id_ecdsa_256.pub"), fixture!("id_ecdsa_521.pub"), fixture!("id_sk_ecdsa.pub")] {
+            let ecdsa = key(line, None);
+            assert_eq!(level(&ecdsa, &KeyPolicy::default()), StrengthLevel::Ok, "{}", ecdsa.algorithm);
+            assert_eq!(level(&ecdsa, &no_nist), StrengthLevel::Warning, "{}", ecdsa.algorithm);
+        }
+        assert_eq!(level(&key(fixture!("id_ed25519.pub"), None), &no_nist), StrengthLevel::Ok);
+    }
+
+    #[test]
+    fn tagged_keys_must_be_security_keys() {
+        let policy = KeyPolicy {
+            require_security_key_tags: vec!["production".to_string()],
+            ..KeyPolicy::default()
+        };
+
+        let report = assess(&key(fixture!("id_ed25519.pub"), Some(" Production ")), &policy);
+        assert_eq!(report.level, StrengthLevel::Warning);
+        assert!(report.issues[0].contains("FIDO security keys"), "{:?}", report.issues);
+        assert!(!report.hardware_backed);
+
+        let report = assess(&key(fixture!("id_sk_ed25519.pub"), Some("production")), &policy);
+        assert_eq!(report.level, StrengthLevel::Ok);
+        assert!(report.hardware_backed);
+
+        assert_eq!(level(&key(fixture!("id_ed25519.pub"), Some("staging")), &policy), StrengthLevel::Ok);
+        // An already weak key stays weak
+        assert_eq!(level(&key(fixture!("id_dsa.pub"), Some("production")), &policy), StrengthLevel::Weak);
+    }
+
+    #[test]
+    fn certificates_are_assessed_by_their_key() {
+        let certificate = key(fixture!("id_ed25519-cert.pub"), None);
+        assert_eq!(level(&certificate, &KeyPolicy::default()), StrengthLevel::Ok);
+    }
+}
@@ -0,0 +1,420 @@
+use aes::Aes256;
+use aes::cipher::{
+    BlockDecrypt,
+    KeyInit,
+    generic_array::GenericArray,
+};
+use aes_gcm::{Aes256Gcm, Nonce};
+use aes_gcm::aead::{Aead, Payload};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine as _, engine::general_purpose};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+
+// On-disk layout of a versioned vault or export file:
+//
+//   magic (6) | version (1) | header length (u16, big endian) | header JSON | ciphertext
+//
+// Everything before the ciphertext is authenticated as associated data, so the
+// header cannot be altered without decryption failing.
+pub const MAGIC: &[u8; 6] = b"SSHKIM";
+
+// Bare AES-256-GCM (nonce + ciphertext) with no header
+const VERSION_BARE_GCM: u8 = 1;
+
+// Self-describing header followed by AES-256-GCM ciphertext
+pub const CURRENT_VERSION: u8 = 2;
+
+pub const CIPHER_AES_256_GCM: &str = "aes-256-gcm";
+
+const GCM_NONCE_LEN: usize = 12;
+
+pub const SALT_LEN: usize = 16;
+
+// Default Argon2id cost for password-derived keys: 64 MiB, 3 passes, 1 lane
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = 64 * 1024;
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = 3;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+// Upper bounds, so that a crafted file header can't exhaust memory or hang
+// the app: 1 GiB, 256 passes, 16 lanes. Also applied to PuTTY keys, whose
+// pass count PuTTYgen picks by timing and can run into the dozens.
+pub(crate) const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+pub(crate) const MAX_ARGON2_ITERATIONS: u32 = 256;
+pub(crate) const MAX_ARGON2_PARALLELISM: u32 = 16;
+
+pub const AUTHENTICATION_FAILED: &str = "Authentication failed: the file was encrypted with a different key, or it is corrupted or has been tampered with";
+
+// Where the encryption key of a file comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    Machine,
+    Password,
+}
+
+// Key derivation function and its parameters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "kebab-case")]
+pub enum Kdf {
+    // The key is not derived from anything stored in the file
+    None,
+    // Single SHA-256 over the password and a constant salt
+    Sha256Legacy,
+    // Memory-hard Argon2id with a per-file random salt
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+impl Kdf {
+    pub fn default_argon2id() -> Self {
+        Kdf::Argon2id {
+            memory_kib: DEFAULT_ARGON2_MEMORY_KIB,
+            iterations: DEFAULT_ARGON2_ITERATIONS,
+            parallelism: DEFAULT_ARGON2_PARALLELISM,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultHeader {
+    pub cipher: String,
+    pub key_source: KeySource,
+    pub kdf: Kdf,
+    // Base64 salt for the KDF, when it uses one
+    pub salt: Option<String>,
+    // Base64 nonce for the cipher
+    pub nonce: String,
+}
+
+// A parsed vault or export file
+#[derive(Debug)]
+pub enum VaultFile<'a> {
+    // Written before headers existed; the key source is unknown
+    Legacy(&'a [u8]),
+    Versioned {
+        version: u8,
+        header: VaultHeader,
+        aad: &'a [u8],
+        ciphertext: &'a [u8],
+    },
+}
+
+impl VaultFile<'_> {
+    pub fn key_source(&self) -> Option<KeySource> {
+        match self {
+            VaultFile::Legacy(_) => None,
+            VaultFile::Versioned { header, .. } => Some(header.key_source),
+        }
+    }
+
+    pub fn header(&self) -> Option<&VaultHeader> {
+        match self {
+            VaultFile::Legacy(_) => None,
+            VaultFile::Versioned { header, .. } => Some(header),
+        }
+    }
+
+    pub fn is_current_version(&self) -> bool {
+        matches!(self, VaultFile::Versioned { version, .. } if *version == CURRENT_VERSION)
+    }
+}
+
+impl VaultHeader {
+    pub fn salt_bytes(&self) -> Result<Option<Vec<u8>>, String> {
+        self.salt.as_ref()
+            .map(|salt| general_purpose::STANDARD.decode(salt)
+                .map_err(|e| format!("Invalid salt in file header: {}", e)))
+            .transpose()
+    }
+}
+
+// Generate a fresh random salt for a password-derived key
+pub fn generate_salt() -> [u8; SALT_LEN] {
+    rand::thread_rng().gen()
+}
+
+// Validate Argon2id cost parameters, from settings or a file header
+pub fn argon2_params(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Params, String> {
+    if memory_kib > MAX_ARGON2_MEMORY_KIB {
+        return Err(format!("Invalid Argon2id parameters: memory cost {} KiB is above the maximum of {} KiB", memory_kib, MAX_ARGON2_MEMORY_KIB));
+    }
+    if iterations > MAX_ARGON2_ITERATIONS {
+        return Err(format!("Invalid Argon2id parameters: {} iterations is above the maximum of {}", iterations, MAX_ARGON2_ITERATIONS));
+    }
+    if parallelism > MAX_ARGON2_PARALLELISM {
+        return Err(format!("Invalid Argon2id parameters: parallelism {} is above the maximum of {}", parallelism, MAX_ARGON2_PARALLELISM));
+    }
+    Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|e| format!("Invalid Argon2id parameters: {}", e))
+}
+
+// Derive a key the way exports were protected before Argon2id. Kept only
+// so that old exports can still be imported.
+pub fn derive_legacy_key(password: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(password.as_bytes());
+    hasher.update(b"ssh-kim-password-salt");
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&hasher.finalize());
+    key
+}
+
+// Derive an encryption key from a password with the given KDF
+pub fn derive_key(kdf: &Kdf, password: &str, salt: Option<&[u8]>) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    match kdf {
+        Kdf::None => return Err("File header does not describe how to derive a key from a password".to_string()),
+        Kdf::Sha256Legacy => key = derive_legacy_key(password),
+        Kdf::Argon2id { memory_kib, iterations, parallelism } => {
+            let salt = salt.ok_or("File header is missing the salt for the Argon2id key derivation")?;
+            let params = argon2_params(*memory_kib, *iterations, *parallelism)?;
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(password.as_bytes(), salt, &mut key)
+                .map_err(|e| format!("Failed to derive key from password: {}", e))?;
+        }
+    }
+    Ok(key)
+}
+
+// Split raw file content into its header and ciphertext
+pub fn parse(bytes: &[u8]) -> Result<VaultFile<'_>, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Ok(VaultFile::Legacy(bytes));
+    }
+
+    let version = *bytes.get(MAGIC.len())
+        .ok_or("Invalid encrypted data: file is truncated")?;
+
+    match version {
+        VERSION_BARE_GCM => Ok(VaultFile::Legacy(bytes)),
+        CURRENT_VERSION => {
+            let len_start = MAGIC.len() + 1;
+            let header_start = len_start + 2;
+            if bytes.len() < header_start {
+                return Err("Invalid encrypted data: file is truncated".to_string());
+            }
+
+            let header_len = u16::from_be_bytes([bytes[len_start], bytes[len_start + 1]]) as usize;
+            let header_end = header_start + header_len;
+            if bytes.len() < header_end {
+                return Err("Invalid encrypted data: file is truncated".to_string());
+            }
+
+            let header: VaultHeader = serde_json::from_slice(&bytes[header_start..header_end])
+                .map_err(|e| format!("Invalid file header: {}", e))?;
+
+            if header.cipher != CIPHER_AES_256_GCM {
+                return Err(format!("Unsupported cipher: {}", header.cipher));
+            }
+
+            Ok(VaultFile::Versioned {
+                version,
+                header,
+                aad: &bytes[..header_end],
+                ciphertext: &bytes[header_end..],
+            })
+        }
+        other => Err(format!("Unsupported file format version: {}. This file was created by a newer version of SSH Kim.", other)),
+    }
+}
+
+// Encrypt data with AES-256-GCM behind a header describing how it was encrypted
+pub fn seal(key: &[u8; 32], key_source: KeySource, kdf: Kdf, salt: Option<&[u8]>, data: &str) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+
+    let mut rng = rand::thread_rng();
+    let nonce: [u8; GCM_NONCE_LEN] = rng.gen();
+
+    let header = VaultHeader {
+        cipher: CIPHER_AES_256_GCM.to_string(),
+        key_source,
+        kdf,
+        salt: salt.map(|s| general_purpose::STANDARD.encode(s)),
+        nonce: general_purpose::STANDARD.encode(nonce),
+    };
+    let header_json = serde_json::to_vec(&header)
+        .map_err(|e| format!("Failed to serialize file header: {}", e))?;
+    let header_len = u16::try_from(header_json.len())
+        .map_err(|_| "File header is too large".to_string())?;
+
+    let mut sealed = MAGIC.to_vec();
+    sealed.push(CURRENT_VERSION);
+    sealed.extend_from_slice(&header_len.to_be_bytes());
+    sealed.extend_from_slice(&header_json);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: data.as_bytes(), aad: &sealed })
+        .map_err(|_| "Failed to encrypt data".to_string())?;
+
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+// Decrypt a parsed file with the given key
+pub fn open(key: &[u8; 32], file: &VaultFile) -> Result<String, String> {
+    match file {
+        VaultFile::Legacy(bytes) => open_legacy(key, bytes),
+        VaultFile::Versioned { header, aad, ciphertext, .. } => {
+            let nonce = general_purpose::STANDARD.decode(&header.nonce)
+                .map_err(|e| format!("Invalid nonce in file header: {}", e))?;
+            if nonce.len() != GCM_NONCE_LEN {
+                return Err("Invalid nonce in file header".to_string());
+            }
+            decrypt_gcm(key, &nonce, ciphertext, aad)
+        }
+    }
+}
+
+// Decrypt files written before the header was introduced
+fn open_legacy(key: &[u8; 32], bytes: &[u8]) -> Result<String, String> {
+    if bytes.starts_with(MAGIC) {
+        // Bare AES-256-GCM: magic | version | nonce | ciphertext
+        let header_len = MAGIC.len() + 1;
+        if bytes.len() < header_len + GCM_NONCE_LEN {
+            return Err("Invalid encrypted data: file is truncated".to_string());
+        }
+        let (aad, rest) = bytes.split_at(header_len);
+        let (nonce, ciphertext) = rest.split_at(GCM_NONCE_LEN);
+        return decrypt_gcm(key, nonce, ciphertext, aad);
+    }
+
+    decrypt_legacy_ecb(key, bytes)
+}
+
+fn decrypt_gcm(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+
+    let decrypted = cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| AUTHENTICATION_FAILED.to_string())?;
+
+    String::from_utf8(decrypted)
+        .map_err(|e| format!("Failed to convert to string: {}", e))
+}
+
+// Decrypt the original base64 AES-ECB format so old files can be migrated
+fn decrypt_legacy_ecb(key: &[u8; 32], bytes: &[u8]) -> Result<String, String> {
+    let cipher = Aes256::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+
+    let encoded = String::from_utf8_lossy(bytes);
+    let decoded = general_purpose::STANDARD.decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+
+    if decoded.len() < 16 || decoded.len() % 16 != 0 {
+        return Err("Invalid encrypted data".to_string());
+    }
+
+    // The legacy format stored an IV but never used it
+    let data = &decoded[16..];
+
+    let mut decrypted = Vec::new();
+
+    for chunk in data.chunks(16) {
+        let mut block = GenericArray::clone_from_slice(chunk);
+        cipher.decrypt_block(&mut block);
+        decrypted.extend_from_slice(block.as_slice());
+    }
+
+    // Remove padding
+    if let Some(&padding) = decrypted.last() {
+        if padding <= 16 && padding > 0 {
+            decrypted.truncate(decrypted.len() - padding as usize);
+        }
+    }
+
+    String::from_utf8(decrypted)
+        .map_err(|e| format!("Failed to convert to string: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    // Written by an independent AES implementation with KEY, in the formats
+    // used before the header existed
+    const LEGACY_ECB: &[u8] = include_bytes!("../tests/fixtures/vault/legacy_ecb.enc");
+    const BARE_GCM: &[u8] = include_bytes!("../tests/fixtures/vault/bare_gcm.enc");
+
+    fn sealed() -> Vec<u8> {
+        seal(&KEY, KeySource::Password, Kdf::default_argon2id(), Some(&[1; SALT_LEN]), "secret data").unwrap()
+    }
+
+    #[test]
+    fn seal_then_open_round_trips() {
+        let sealed = sealed();
+        let file = parse(&sealed).unwrap();
+        assert!(file.is_current_version());
+        assert_eq!(file.key_source(), Some(KeySource::Password));
+        assert_eq!(file.header().unwrap().salt_bytes().unwrap(), Some(vec![1; SALT_LEN]));
+        assert_eq!(open(&KEY, &file).unwrap(), "secret data");
+    }
+
+    #[test]
+    fn open_rejects_flipped_ciphertext() {
+        let mut sealed = sealed();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(open(&KEY, &parse(&sealed).unwrap()).unwrap_err(), AUTHENTICATION_FAILED);
+    }
+
+    #[test]
+    fn open_rejects_flipped_header() {
+        // The salt isn't used to open the file with a known key, so only
+        // the header's authentication can catch the change
+        let sealed = sealed();
+        let salt = general_purpose::STANDARD.encode([1; SALT_LEN]);
+        let position = sealed.windows(salt.len()).position(|w| w == salt.as_bytes()).unwrap();
+        let mut tampered = sealed.clone();
+        tampered[position] = if tampered[position] == b'A' { b'B' } else { b'A' };
+
+        let file = parse(&tampered).unwrap();
+        assert_ne!(file.header().unwrap().salt, Some(salt));
+        assert_eq!(open(&KEY, &file).unwrap_err(), AUTHENTICATION_FAILED);
+    }
+
+    #[test]
+    fn open_rejects_wrong_key() {
+        let sealed = sealed();
+        assert_eq!(open(&[8; 32], &parse(&sealed).unwrap()).unwrap_err(), AUTHENTICATION_FAILED);
+    }
+
+    #[test]
+    fn opens_legacy_ecb_files() {
+        let file = parse(LEGACY_ECB).unwrap();
+        assert!(matches!(file, VaultFile::Legacy(_)));
+        assert_eq!(file.key_source(), None);
+        assert_eq!(open(&KEY, &file).unwrap(), r#"[{"name":"legacy ecb"}]"#);
+    }
+
+    #[test]
+    fn opens_bare_gcm_files() {
+        let file = parse(BARE_GCM).unwrap();
+        assert!(!file.is_current_version());
+        assert_eq!(open(&KEY, &file).unwrap(), r#"[{"name":"bare gcm"}]"#);
+        assert_eq!(open(&[8; 32], &file).unwrap_err(), AUTHENTICATION_FAILED);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(parse(b"SSHKIM\x09").unwrap_err().contains("newer version"));
+    }
+
+    #[test]
+    fn argon2_params_are_bounded() {
+        assert!(argon2_params(DEFAULT_ARGON2_MEMORY_KIB, DEFAULT_ARGON2_ITERATIONS, DEFAULT_ARGON2_PARALLELISM).is_ok());
+        assert!(argon2_params(MAX_ARGON2_MEMORY_KIB, MAX_ARGON2_ITERATIONS, MAX_ARGON2_PARALLELISM).is_ok());
+        assert!(argon2_params(MAX_ARGON2_MEMORY_KIB + 1, 1, 1).unwrap_err().contains("memory"));
+        assert!(argon2_params(DEFAULT_ARGON2_MEMORY_KIB, MAX_ARGON2_ITERATIONS + 1, 1).unwrap_err().contains("iterations"));
+        assert!(argon2_params(DEFAULT_ARGON2_MEMORY_KIB, 1, MAX_ARGON2_PARALLELISM + 1).unwrap_err().contains("parallelism"));
+        assert!(argon2_params(u32::MAX, u32::MAX, u32::MAX).is_err());
+    }
+}
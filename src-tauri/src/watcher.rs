@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use notify::{Event, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use crate::commands;
+
+pub const VAULT_CHANGED_EVENT: &str = "vault-changed";
+pub const SSH_DIR_CHANGED_EVENT: &str = "ssh-dir-changed";
+
+// Editors and ssh-keygen touch files several times per change; wait for the
+// burst to settle before reporting it
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(300);
+
+// How often the watched directories are re-checked, so a switched keys file
+// or a newly created ~/.ssh gets picked up
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Public key files in an SSH directory, with their modification times
+type KeyFiles = HashMap<String, Option<SystemTime>>;
+
+// Public key files added, removed or rewritten in an SSH directory
+#[derive(Serialize, Clone)]
+pub struct SshDirChange {
+    pub path: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl SshDirChange {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+// Watch the active keys file and the common SSH directories in the
+// background, emitting "vault-changed" and "ssh-dir-changed" to the frontend
+pub fn start(app: AppHandle) {
+    thread::spawn(move || {
+        if let Err(e) = run(&app) {
+            println!("File watcher stopped: {}", e);
+        }
+    });
+}
+
+fn run(app: &AppHandle) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+    let mut watched: HashSet<PathBuf> = HashSet::new();
+    let mut snapshots: HashMap<PathBuf, KeyFiles> = HashMap::new();
+
+    loop {
+        // The keys file's directory is watched rather than the file itself,
+        // since atomic saves replace the file
+        let keys_files = commands::get_keys_file_path()
+            .map(|path| resolve_keys_file(&path))
+            .unwrap_or_default();
+        let ssh_dirs: Vec<PathBuf> = commands::get_common_ssh_locations()
+            .into_iter()
+            .filter(|dir| dir.is_dir())
+            .collect();
+        // Events name resolved paths, which differ for symlinked directories
+        let resolved_ssh_dirs: Vec<PathBuf> = ssh_dirs.iter()
+            .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+            .collect();
+
+        snapshots.retain(|dir, _| ssh_dirs.contains(dir));
+        for dir in &ssh_dirs {
+            snapshots.entry(dir.clone()).or_insert_with(|| snapshot_key_files(dir));
+        }
+
+        let mut wanted: HashSet<PathBuf> = resolved_ssh_dirs.iter().cloned().collect();
+        wanted.extend(keys_files.iter().filter_map(|path| path.parent()).map(Path::to_path_buf));
+
+        for dir in watched.difference(&wanted) {
+            let _ = watcher.unwatch(dir);
+        }
+        for dir in wanted.difference(&watched) {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                println!("Failed to watch {}: {}", dir.display(), e);
+            }
+        }
+        watched = wanted;
+
+        let first = match rx.recv_timeout(REFRESH_INTERVAL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Err("file watcher disconnected".to_string()),
+        };
+
+        let mut changed_paths: HashSet<PathBuf> = HashSet::new();
+        collect_paths(first, &mut changed_paths);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE_INTERVAL) {
+            collect_paths(event, &mut changed_paths);
+        }
+
+        if keys_files.iter().any(|path| changed_paths.contains(path)) {
+            if let Some(change) = commands::reload_vault_if_changed() {
+                println!("Keys file changed on disk: {}", change.path);
+                let _ = app.emit(VAULT_CHANGED_EVENT, change);
+            }
+        }
+
+        for (dir, resolved_dir) in ssh_dirs.iter().zip(&resolved_ssh_dirs) {
+            if !changed_paths.iter().any(|path| path.parent() == Some(resolved_dir.as_path())) {
+                continue;
+            }
+
+            let current = snapshot_key_files(dir);
+            let change = diff_key_files(dir, &snapshots[dir], &current);
+            snapshots.insert(dir.clone(), current);
+
+            if !change.is_empty() {
+                println!("SSH directory changed: {}", change.path);
+                let _ = app.emit(SSH_DIR_CHANGED_EVENT, change);
+            }
+        }
+    }
+}
+
+// The paths that changes to the keys file show up under: the file in its
+// resolved directory (a relative path has the current directory as parent),
+// and the target of the file if it is a symlink, which saves replace but
+// other programs may write through
+fn resolve_keys_file(path: &Path) -> Vec<PathBuf> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let (Some(file_name), Ok(dir)) = (path.file_name(), fs::canonicalize(dir)) else {
+        return Vec::new();
+    };
+
+    let mut paths = vec![dir.join(file_name)];
+    if let Ok(target) = fs::canonicalize(path) {
+        if !paths.contains(&target) {
+            paths.push(target);
+        }
+    }
+    paths
+}
+
+// Reads show up as access events, including our own reads of the keys file
+fn collect_paths(event: notify::Result<Event>, paths: &mut HashSet<PathBuf>) {
+    match event {
+        Ok(event) if !event.kind.is_access() => paths.extend(event.paths),
+        Ok(_) => {}
+        Err(e) => println!("File watcher error: {}", e),
+    }
+}
+
+fn snapshot_key_files(dir: &Path) -> KeyFiles {
+    commands::scan_directory_for_keys(dir)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+fn diff_key_files(dir: &Path, previous: &KeyFiles, current: &KeyFiles) -> SshDirChange {
+    let mut change = SshDirChange {
+        path: dir.to_string_lossy().to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+    };
+
+    for (path, modified) in current {
+        match previous.get(path) {
+            None => change.added.push(path.clone()),
+            Some(old) if old != modified => change.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    change.removed = previous.keys()
+        .filter(|path| !current.contains_key(*path))
+        .cloned()
+        .collect();
+
+    change.added.sort();
+    change.removed.sort();
+    change.modified.sort();
+    change
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn resolves_relative_keys_files() {
+        let resolved = resolve_keys_file(Path::new("keys.enc"));
+        assert_eq!(resolved, [std::env::current_dir().unwrap().canonicalize().unwrap().join("keys.enc")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_symlinked_keys_files() {
+        let root = std::env::temp_dir().join(format!("ssh-kim-watcher-{}", Uuid::new_v4().simple()));
+        let real_dir = root.join("real");
+        fs::create_dir_all(&real_dir).unwrap();
+        std::os::unix::fs::symlink(&real_dir, root.join("link")).unwrap();
+        fs::write(real_dir.join("target.enc"), b"").unwrap();
+        std::os::unix::fs::symlink(real_dir.join("target.enc"), real_dir.join("keys.enc")).unwrap();
+        let real_dir = real_dir.canonicalize().unwrap();
+
+        let resolved = resolve_keys_file(&root.join("link").join("keys.enc"));
+        let _ = fs::remove_dir_all(&root);
+        assert_eq!(resolved, [real_dir.join("keys.enc"), real_dir.join("target.enc")]);
+    }
+}
@@ -1,5 +1,6 @@
 import { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { 
   Key, 
   Plus, 
@@ -15,7 +16,8 @@ import {
   Check,
   Settings,
   Folder,
-  Save
+  Save,
+  Usb
 } from 'lucide-react';
 import './App.css';
 
@@ -47,6 +49,10 @@ function App() {
   const [showExportPasswordModal, setShowExportPasswordModal] = useState(false);
   const [exportPasswordModalPassword, setExportPasswordModalPassword] = useState('');
   const [importResult, setImportResult] = useState(null);
+  const [showUnlockModal, setShowUnlockModal] = useState(false);
+  const [unlockPassword, setUnlockPassword] = useState('');
+  const [unlockError, setUnlockError] = useState('');
+  const [unlocking, setUnlocking] = useState(false);
   const [exportResult, setExportResult] = useState(null);
 
 
@@ -63,6 +69,27 @@ function App() {
     loadKeysFilePath();
   }, []);
 
+  // Reload when the keys file is changed by another instance or a sync client,
+  // and rescan when key files appear, disappear or change in an SSH directory
+  useEffect(() => {
+    const unlisteners = [
+      listen('vault-changed', (event) => {
+        if (event.payload.error) {
+          console.error('Failed to reload the changed keys file:', event.payload.error);
+        }
+        loadKeys();
+      }),
+      listen('ssh-dir-changed', async () => {
+        try {
+          setScannedLocations(await invoke('scan_ssh_locations'));
+        } catch (error) {
+          console.error('Failed to rescan for public keys:', error);
+        }
+      }),
+    ];
+    return () => unlisteners.forEach(unlisten => unlisten.then(stop => stop()));
+  }, []);
+
   const loadKeysFilePath = async () => {
     try {
       const path = await invoke('get_keys_file_location');
@@ -191,6 +218,13 @@ function App() {
     } catch (error) {
       console.error('Failed to load keys:', error);
       
+      // The vault has a master password and is locked
+      if (error.startsWith('VAULT_LOCKED')) {
+        setKeys([]);
+        setShowUnlockModal(true);
+        return;
+      }
+      
       // Check if the error is due to file not found
       if (error.includes('Failed to read keys file') || 
           error.includes('No such file or directory') ||
@@ -205,6 +239,22 @@ function App() {
     }
   };
 
+  const handleUnlock = async () => {
+    try {
+      setUnlocking(true);
+      const result = await invoke('unlock_vault', { password: unlockPassword });
+      setKeys(result);
+      setUnlockPassword('');
+      setUnlockError('');
+      setShowUnlockModal(false);
+    } catch (error) {
+      console.error('Failed to unlock vault:', error);
+      setUnlockError(String(error));
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
   const handleAddKey = async () => {
     try {
       setErrorMessage(''); // Clear any previous errors
@@ -238,7 +288,7 @@ function App() {
         if (error.includes('name already exists')) {
           setErrorMessage('A key with this name already exists. Please choose a different name.');
         } else if (error.includes('content already exists')) {
-          setErrorMessage('This SSH key content already exists in your collection. Please check if you have already added this key.');
+          setErrorMessage(error.replace('A key with this content already exists', 'This SSH key is already in your collection as'));
         } else {
           setErrorMessage('This key already exists in your collection.');
         }
@@ -281,7 +331,7 @@ function App() {
         if (error.includes('name already exists')) {
           setErrorMessage('A key with this name already exists. Please choose a different name.');
         } else if (error.includes('content already exists')) {
-          setErrorMessage('This SSH key content already exists in your collection. Please check if you have already added this key.');
+          setErrorMessage(error.replace('A key with this content already exists', 'This SSH key is already in your collection as'));
         } else {
           setErrorMessage('This key already exists in your collection.');
         }
@@ -331,9 +381,14 @@ function App() {
 
   const handleSelectFile = async (filePath) => {
     try {
-      const content = await invoke('read_ssh_key_file', { filePath });
-      setFormData(prev => ({ ...prev, keyContent: content }));
+      const file = await invoke('read_ssh_key_file', { filePath });
+      setFormData(prev => ({ ...prev, keyContent: file.content }));
       setSelectedLocation(filePath);
+      if (file.private_key_format) {
+        alert('This is a private key file. Only its public key will be added.');
+      } else if (file.matches_private_key === false) {
+        alert('Warning: this public key does not match the private key next to it.');
+      }
     } catch (error) {
       console.error('Failed to read file:', error);
       alert('Failed to read file: ' + error);
@@ -350,10 +405,16 @@ function App() {
     }
   };
 
+  // FIDO security key (sk-*) keys also match searches for these
+  const hardwareKeyTerms = ['hardware', 'fido', 'security key'];
+
   const filteredKeys = keys.filter(key =>
     key.name.toLowerCase().includes(searchTerm.toLowerCase()) ||
     (key.tag && key.tag.toLowerCase().includes(searchTerm.toLowerCase())) ||
-    key.key_type.toLowerCase().includes(searchTerm.toLowerCase())
+    key.key_type.toLowerCase().includes(searchTerm.toLowerCase()) ||
+    (key.fingerprint && key.fingerprint.includes(searchTerm.trim())) ||
+    (key.fingerprint_md5 && key.fingerprint_md5.toLowerCase().includes(searchTerm.trim().toLowerCase())) ||
+    (key.security_key && hardwareKeyTerms.some(term => term.includes(searchTerm.trim().toLowerCase())))
   );
 
   const openAddModal = async () => {
@@ -511,6 +572,7 @@ function App() {
         totalImported: importResult.imported_count,
         newKeys: importResult.imported_count,
         duplicates: importResult.duplicate_count,
+        duplicateMatches: importResult.duplicates,
         totalInStore: importResult.total_in_store
       });
       
@@ -564,7 +626,7 @@ function App() {
           <Search size={20} />
           <input
             type="text"
-            placeholder="Search keys by name, tag, or type..."
+            placeholder="Search keys by name, tag, type, or fingerprint..."
             value={searchTerm}
             onChange={(e) => setSearchTerm(e.target.value)}
           />
@@ -601,6 +663,18 @@ function App() {
                             {key.tag}
                           </span>
                         )}
+                        {key.security_key && (
+                          <span className="key-security" title={`FIDO application: ${key.security_key.application}`}>
+                            <Usb size={14} />
+                            Security key
+                          </span>
+                        )}
+                        {key.has_private_key && (
+                          <span className="key-private">
+                            <Key size={14} />
+                            {key.private_key_encrypted ? 'Private key (passphrase)' : 'Private key'}
+                          </span>
+                        )}
                         <span className="key-date">
                           <Calendar size={14} />
                           {formatDate(key.last_modified)}
@@ -645,6 +719,16 @@ function App() {
                   <div className="key-content">
                     <pre>{key.key}</pre>
                   </div>
+                  {key.fingerprint && (
+                    <div className="key-fingerprint">{key.fingerprint}</div>
+                  )}
+                  {key.certificate && (
+                    <div className="key-certificate">
+                      Certificate "{key.certificate.key_id}" for {key.certificate.principals.join(', ') || 'any principal'}
+                      {key.certificate.valid_before && `, expires ${new Date(key.certificate.valid_before).toLocaleDateString()}`}
+                      , signed by {key.certificate.ca_fingerprint}
+                    </div>
+                  )}
                 </div>
               ))
             )}
@@ -692,8 +776,9 @@ function App() {
                           {location.keys.map((keyPath, keyIndex) => (
                             <button
                               key={keyIndex}
-                              className={`key-file-button ${selectedLocation === keyPath ? 'selected' : ''}`}
+                              className={`key-file-button ${selectedLocation === keyPath ? 'selected' : ''} ${location.mismatched_keys.includes(keyPath) ? 'mismatched' : ''}`}
                               onClick={() => handleSelectFile(keyPath)}
+                              title={location.mismatched_keys.includes(keyPath) ? 'Does not match the private key next to it' : undefined}
                             >
                               {keyPath.split('/').pop()}
                             </button>
@@ -874,6 +959,11 @@ function App() {
                     <p><strong>File:</strong> {importResult.file}</p>
                     <p><strong>Keys imported:</strong> {importResult.newKeys}</p>
                     <p><strong>Duplicates ignored:</strong> {importResult.duplicates}</p>
+                    {importResult.duplicateMatches && importResult.duplicateMatches.map((duplicate, index) => (
+                      <p key={index} className="import-result-duplicate">
+                        {duplicate.name} matches existing key "{duplicate.existing_name}"
+                      </p>
+                    ))}
                     <p><strong>Total keys in store:</strong> {importResult.totalInStore}</p>
                   </div>
                 </div>
@@ -1003,6 +1093,61 @@ function App() {
         </div>
       )}
 
+      {/* Unlock Vault Modal */}
+      {showUnlockModal && (
+        <div className="modal-overlay">
+          <div className="modal">
+            <div className="modal-header">
+              <h2>Unlock Keys</h2>
+              <button onClick={() => setShowUnlockModal(false)}>×</button>
+            </div>
+            <div className="modal-content">
+              {unlockError && (
+                <div className="error-message">
+                  {unlockError}
+                </div>
+              )}
+              <div className="form-group">
+                <label>Master Password</label>
+                <input
+                  type="password"
+                  value={unlockPassword}
+                  onChange={(e) => {
+                    setUnlockPassword(e.target.value);
+                    if (unlockError) {
+                      setUnlockError('');
+                    }
+                  }}
+                  placeholder="Enter the master password"
+                  className="password-input"
+                  autoFocus
+                  onKeyPress={(e) => {
+                    if (e.key === 'Enter' && unlockPassword) {
+                      handleUnlock();
+                    }
+                  }}
+                />
+                <small className="file-help">
+                  💡 The keys file is protected with a master password and is locked
+                </small>
+              </div>
+              <div className="modal-footer">
+                <button onClick={() => setShowUnlockModal(false)}>
+                  Cancel
+                </button>
+                <button
+                  onClick={handleUnlock}
+                  disabled={unlocking || !unlockPassword}
+                  className="primary"
+                >
+                  {unlocking ? 'Unlocking...' : 'Unlock'}
+                </button>
+              </div>
+            </div>
+          </div>
+        </div>
+      )}
+
       {/* Export Password Modal */}
       {showExportPasswordModal && (
         <div className="modal-overlay">
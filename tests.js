@@ -103,6 +103,7 @@ async function testPasswordProtectedExportImport() {
       keys: importResult.keys.length,
       imported: importResult.imported_count,
       duplicates: importResult.duplicate_count,
+      matched: importResult.duplicates.map(d => `${d.name} -> ${d.existing_name}`),
       total: importResult.total_in_store
     });
     
@@ -113,6 +114,195 @@ async function testPasswordProtectedExportImport() {
   }
 }
 
+// ============================================================================
+// MASTER PASSWORD TESTS
+// ============================================================================
+
+async function testMasterPasswordMode() {
+  try {
+    console.log('🧪 Testing Master Password Mode...');
+    
+    // Test 1: Enable master password
+    console.log('\n1. Enabling master password...');
+    const testPassword = 'master-test-123';
+    await window.__TAURI__.invoke('set_encryption_password', { password: testPassword });
+    const mode = await window.__TAURI__.invoke('get_encryption_mode');
+    console.log('✅ Encryption mode:', mode);
+    
+    // Test 2: Unlock with the wrong password
+    console.log('\n2. Unlocking with a wrong password...');
+    try {
+      await window.__TAURI__.invoke('unlock_vault', { password: 'wrong-password' });
+      console.log('❌ Unlock with a wrong password should have failed');
+    } catch (error) {
+      console.log('✅ Wrong password rejected:', error);
+    }
+    
+    // Test 3: Unlock with the right password
+    console.log('\n3. Unlocking with the master password...');
+    const keys = await window.__TAURI__.invoke('unlock_vault', { password: testPassword });
+    console.log('✅ Unlocked, keys count:', keys.length);
+    
+    // Test 4: Lock the vault and check reads are refused
+    console.log('\n4. Locking the vault...');
+    await window.__TAURI__.invoke('lock_vault');
+    try {
+      await window.__TAURI__.invoke('get_ssh_keys');
+      console.log('❌ Reading keys from a locked vault should have failed');
+    } catch (error) {
+      console.log(String(error).startsWith('VAULT_LOCKED') ? '✅ Locked vault refused read:' : '❌ Unexpected error:', error);
+    }
+    await window.__TAURI__.invoke('unlock_vault', { password: testPassword });
+    
+    // Test 5: Disable master password
+    console.log('\n5. Disabling master password...');
+    await window.__TAURI__.invoke('clear_encryption_password');
+    const finalMode = await window.__TAURI__.invoke('get_encryption_mode');
+    console.log('✅ Encryption mode:', finalMode);
+    
+    console.log('\n🎉 Master password test completed successfully!');
+    
+  } catch (error) {
+    console.error('❌ Master password test failed:', error);
+  }
+}
+
+// ============================================================================
+// BACKUP TESTS
+// ============================================================================
+
+async function testBackups() {
+  try {
+    console.log('🧪 Testing Backups...');
+    
+    // Test 1: List backups of the current keys file
+    console.log('\n1. Listing backups...');
+    const backups = await window.__TAURI__.invoke('list_backups');
+    console.log('✅ Backups found:', backups.length);
+    
+    if (backups.length === 0) {
+      console.log('ℹ️ No backups yet; make a change to a key to create one');
+      return;
+    }
+    
+    // Test 2: Preview the newest backup
+    console.log('\n2. Previewing newest backup...');
+    const preview = await window.__TAURI__.invoke('preview_backup', { fileName: backups[0].file_name });
+    console.log('✅ Backup preview:', {
+      file: preview.file_name,
+      created: preview.created,
+      keys: preview.key_count,
+      mode: preview.encryption_mode
+    });
+    
+    // Test 3: Check the backup limit
+    console.log('\n3. Getting backup limit...');
+    const limit = await window.__TAURI__.invoke('get_backup_limit');
+    console.log('✅ Backup limit:', limit);
+    
+    console.log('\n🎉 Backup test completed successfully!');
+    
+  } catch (error) {
+    console.error('❌ Backup test failed:', error);
+  }
+}
+
+// ============================================================================
+// MERGE PREVIEW TESTS
+// ============================================================================
+
+async function testMergePreview() {
+  try {
+    console.log('🧪 Testing Merge Preview...');
+    
+    // Test 1: Export the current keys so every key in the file matches one in the store
+    console.log('\n1. Exporting keys to test file...');
+    const testExportPath = '/tmp/test_merge_preview.enc';
+    await window.__TAURI__.invoke('export_keys_to_file', { filePath: testExportPath });
+    console.log('✅ Exported keys to:', testExportPath);
+    
+    // Test 2: Preview merging the file back
+    console.log('\n2. Previewing merge...');
+    const plan = await window.__TAURI__.invoke('preview_merge', { filePath: testExportPath, password: null });
+    console.log('✅ Merge plan:', plan.map(entry => `${entry.incoming.name}: ${entry.status} -> ${entry.resolution}`));
+    
+    // Test 3: Apply the merge, keeping local entries
+    console.log('\n3. Applying merge with keep_local for every key...');
+    const resolutions = Object.fromEntries(plan.map(entry => [entry.incoming.id, 'keep_local']));
+    const result = await window.__TAURI__.invoke('apply_merge', { filePath: testExportPath, password: null, resolutions });
+    console.log('✅ Merge result:', {
+      added: result.added_count,
+      updated: result.updated_count,
+      unchanged: result.unchanged_count,
+      total: result.total_in_store
+    });
+    
+    console.log('\n🎉 Merge preview test completed successfully!');
+    
+  } catch (error) {
+    console.error('❌ Merge preview test failed:', error);
+  }
+}
+
+// ============================================================================
+// KEY GENERATION AND CERTIFICATE TESTS
+// ============================================================================
+
+async function testKeyGeneration() {
+  const created = [];
+  try {
+    console.log('🧪 Testing Key Generation...');
+    
+    // Test 1: Generate a key pair of each type
+    console.log('\n1. Generating key pairs...');
+    for (const [keyType, bits] of [['ed25519', null], ['ecdsa', 384], ['rsa', 3072]]) {
+      const generated = await window.__TAURI__.invoke('generate_ssh_key', {
+        name: `Test Generated ${keyType}`,
+        tag: 'test',
+        keyType,
+        bits,
+        comment: 'test@example.com',
+        passphrase: null,
+        fileName: null
+      });
+      created.push(generated.key);
+      console.log('✅ Generated:', generated.key.algorithm, generated.key.fingerprint);
+    }
+    
+    // Test 2: Use the ed25519 key as a CA and sign the ECDSA key
+    console.log('\n2. Signing a certificate...');
+    const [ca, subject] = created;
+    await window.__TAURI__.invoke('set_certificate_authority', { id: ca.id, privateKey: null });
+    const certificate = await window.__TAURI__.invoke('sign_certificate', {
+      caId: ca.id,
+      keyId: subject.id,
+      request: { cert_type: 'user', key_id: 'test@example.com', principals: ['test'] },
+      passphrase: null,
+      outputPath: null
+    });
+    created.push(certificate);
+    console.log('✅ Signed certificate serial', certificate.certificate.serial, 'for', certificate.certificate.principals);
+    
+    // Test 3: Revoke the certificate and check it against a KRL
+    console.log('\n3. Generating and checking a KRL...');
+    await window.__TAURI__.invoke('revoke_key', { id: certificate.id, reason: 'test' });
+    const krlPath = '/tmp/test_revoked.krl';
+    const summary = await window.__TAURI__.invoke('generate_krl', { filePath: krlPath, comment: null });
+    console.log('✅ KRL written:', summary);
+    const check = await window.__TAURI__.invoke('check_krl', { filePath: krlPath, keyContent: certificate.key });
+    console.log(check.revoked ? '✅ Certificate is revoked:' : '❌ Certificate is not revoked', check.reason);
+    
+    console.log('\n🎉 Key generation test completed successfully!');
+    
+  } catch (error) {
+    console.error('❌ Key generation test failed:', error);
+  } finally {
+    for (const key of created) {
+      await window.__TAURI__.invoke('remove_ssh_key', { id: key.id }).catch(() => {});
+    }
+  }
+}
+
 // ============================================================================
 // FILE DIALOG TESTS
 // ============================================================================
@@ -151,10 +341,31 @@ async function testKeyManagement() {
     const testKey = {
       name: 'Test Key',
       tag: 'test',
-      keyContent: 'ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC... test@example.com'
+      keyContent: 'ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQCOSdJQfZtmIHsRYYkrc96TQSk+pfoceYvHwGZV9Ox8DuSDa6rFGrhQaf19HiMzanvFod4otV24xUM0ImxZfeVHSGBMqwjucTrr2l1knfz/iVjHbCEN0+kNvepWtn8NFtY64/JF/VlY/h3jmMV40bf7xLeNhH4ml5A/OkCHIIB8npLOgRLfTRMtrycFVwOjnFhsXt502oIC6wdUACGKIc7wRbFOQ5mPlxFgodCwvHODF3arr+D4RuH3pv3gXJFJsuScfND9F8VsBe2raLB5dLnUafizd2Ia3tDutxxEo/VG+R2dT1085C5T6J+Zr3IVkTS5FVthDubtL3HsO+tHykG5 test@example.com'
     };
     const newKey = await window.__TAURI__.invoke('add_ssh_key', testKey);
-    console.log('✅ Added test key:', newKey.id);
+    console.log('✅ Added test key:', newKey.id, `(${newKey.algorithm}, ${newKey.bits} bits, comment: ${newKey.comment})`);
+    
+    // Test 1a: Randomart and strength report
+    console.log('\n1a. Getting randomart and strength report...');
+    const randomart = await window.__TAURI__.invoke('get_key_randomart', { id: newKey.id });
+    console.log('✅ Randomart:\n' + randomart);
+    const report = await window.__TAURI__.invoke('get_key_strength_report');
+    const strength = report.find(entry => entry.id === newKey.id);
+    console.log('✅ Strength:', strength.level, strength.issues);
+    
+    // Test 1b: Malformed keys are rejected
+    console.log('\n1b. Adding malformed key...');
+    try {
+      await window.__TAURI__.invoke('add_ssh_key', {
+        name: 'Malformed Key',
+        tag: 'test',
+        keyContent: 'not a key, but mentions ssh-rsa'
+      });
+      console.log('❌ Malformed key was accepted');
+    } catch (error) {
+      console.log('✅ Malformed key rejected:', error);
+    }
     
     // Test 2: Update the key
     console.log('\n2. Updating test key...');
@@ -190,6 +401,10 @@ async function runAllTests() {
   await testCoreCommands();
   await testExportImportFunctionality();
   await testPasswordProtectedExportImport();
+  await testMasterPasswordMode();
+  await testBackups();
+  await testMergePreview();
+  await testKeyGeneration();
   await testFileDialogs();
   await testKeyManagement();
   
@@ -209,9 +424,13 @@ Available Tests:
 1. testCoreCommands() - Test basic functionality
 2. testExportImportFunctionality() - Test file export/import
 3. testPasswordProtectedExportImport() - Test password-protected export/import
-4. testFileDialogs() - Test file dialogs
-5. testKeyManagement() - Test key CRUD operations
-6. runAllTests() - Run all tests
+4. testMasterPasswordMode() - Test master password enable/lock/unlock/disable
+5. testBackups() - Test backup listing and preview
+6. testMergePreview() - Test merge preview and conflict resolution
+7. testKeyGeneration() - Test key generation, certificate signing and KRLs
+8. testFileDialogs() - Test file dialogs
+9. testKeyManagement() - Test key CRUD operations
+10. runAllTests() - Run all tests
 
 Usage: Call any function in the browser console when the app is running.
 Example: testCoreCommands()